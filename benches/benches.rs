@@ -97,6 +97,35 @@ fn disk_set_lookup(c: &mut Criterion) {
     }
 }
 
+#[cfg(not(feature = "rayon"))]
+fn par_set_lookup(_c: &mut Criterion) {}
+
+#[cfg(feature = "rayon")]
+fn par_set_lookup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("US915 HexTreeSet batch lookup");
+
+    let us915_set: HexTreeSet = COMPACT_US915_INDICES
+        .iter()
+        .map(|&idx| Cell::try_from(idx).unwrap())
+        .collect();
+    let queries: Vec<Cell> = PLAIN_US915_INDICES
+        .iter()
+        .take(1_000_000)
+        .map(|&idx| Cell::try_from(idx).unwrap())
+        .collect();
+
+    group.bench_function("serial", |b| {
+        b.iter(|| {
+            queries
+                .iter()
+                .map(|&cell| us915_set.contains(cell))
+                .collect::<Vec<bool>>()
+        })
+    });
+
+    group.bench_function("parallel", |b| b.iter(|| us915_set.par_contains(&queries)));
+}
+
 fn set_construction(c: &mut Criterion) {
     let mut group = c.benchmark_group("US915 HexTreeSet construction");
 
@@ -306,6 +335,7 @@ criterion_group!(
     benches,
     set_lookup,
     disk_set_lookup,
+    par_set_lookup,
     subtree_iter,
     map_lookup,
     set_iteration,
@@ -3,7 +3,7 @@
 //! you create invalid H3 indices. Cell is higher level and enforces
 //! invariants.
 
-use crate::{Error, Result};
+use crate::{digits::Digits, Error, Result};
 use std::{convert::TryFrom, fmt};
 
 /// A low-level type for H3 [index manipulation].
@@ -138,13 +138,28 @@ impl Index {
 )]
 pub struct Cell(pub(crate) u64);
 
+/// Returns `true` if any of `raw`'s digits from res 1 through its
+/// resolution is 7, which H3 reserves for digits past the resolution.
+const fn has_unused_digit(raw: u64) -> bool {
+    let res = (raw >> 0x34) as u8 & 0b1111;
+    let mut r = 1;
+    while r <= res {
+        if (raw >> ((15 - r) * 3)) & 0b111 == 7 {
+            return true;
+        }
+        r += 1;
+    }
+    false
+}
+
 impl Cell {
     /// Constructs a new Cell from a raw [u64] H3 index.
     ///
     /// # Errors
     ///
     /// Returns an error if u64 is not a valid [bit-representation] of
-    /// an H3 cell (mode 1 H3 index).
+    /// an H3 cell (mode 1 H3 index), including when any digit along
+    /// its path is the unused value 7.
     ///
     /// [bit-representation]: https://h3geo.org/docs/core-library/h3Indexing/
     #[inline]
@@ -156,7 +171,9 @@ impl Cell {
         // we only care about mode 1 (cell) indicies
         idx.mode() == 1 &&
         // there are only 122 base cells
-        idx.base() < 122
+        idx.base() < 122 &&
+        // digits 1..=res must each be in [0,7)
+        !has_unused_digit(raw)
         {
             Ok(Cell(idx.0))
         } else {
@@ -189,6 +206,69 @@ impl Cell {
         }
     }
 
+    /// Returns the res-0 cell for the provided `base`.
+    #[inline]
+    pub(crate) const fn from_base(base: u8) -> Self {
+        Cell(Index(0x8001fffffffffff).set_base(base).0)
+    }
+
+    /// Returns this cell's child at the next finer resolution with
+    /// the provided `digit`.
+    ///
+    /// Returns None if this is a res-15 cell. Note that this does not
+    /// check whether `digit` is the deleted subsequence of a
+    /// pentagon.
+    #[inline]
+    pub(crate) const fn to_child(self, digit: u8) -> Option<Self> {
+        let res = self.res();
+        if res == 15 {
+            None
+        } else {
+            let idx = Index(self.0).set_res(res + 1).set_digit(res + 1, digit);
+            Some(Cell(idx.0))
+        }
+    }
+
+    /// Returns `true` if this cell is one of the 12 pentagons present
+    /// at every resolution.
+    ///
+    /// Pentagons have 6 children instead of 7 as they lack a child
+    /// with the digit 1.
+    #[inline]
+    pub fn is_pentagon(&self) -> bool {
+        const PENTAGON_BASES: [u8; 12] = [4, 14, 24, 38, 49, 58, 63, 72, 83, 97, 107, 117];
+        PENTAGON_BASES.contains(&self.base()) && Digits::new(*self).all(|digit| digit == 0)
+    }
+
+    /// Returns the number of this cell's descendants at `res`.
+    ///
+    /// Returns 0 if this cell is finer than `res`.
+    #[inline]
+    pub(crate) fn descendant_count(&self, res: u8) -> u64 {
+        debug_assert!(res < 16);
+        match res.checked_sub(self.res()) {
+            None => 0,
+            // A pentagon has 1 pentagon and 5 hexagon children.
+            Some(depth) if self.is_pentagon() => 1 + 5 * (7_u64.pow(depth as u32) - 1) / 6,
+            Some(depth) => 7_u64.pow(depth as u32),
+        }
+    }
+
+    /// Returns the smallest and largest raw indices of this cell's
+    /// descendants at `res`, which must not be coarser than this
+    /// cell.
+    #[inline]
+    pub(crate) fn descendant_index_range(&self, res: u8) -> (u64, u64) {
+        debug_assert!(res >= self.res());
+        let mut low = Index(self.0).set_res(res);
+        let mut high = low;
+        for child_res in self.res() + 1..=res {
+            low = low.set_digit(child_res, 0);
+            high = high.set_digit(child_res, 6);
+        }
+        (low.0, high.0)
+    }
+
     /// Returns this cell's base (res-0 parent).
     #[inline]
     pub(crate) const fn base(&self) -> u8 {
@@ -305,6 +385,45 @@ impl CellStack {
     }
 }
 
+/// An iterator over a cell's descendants at a finer resolution, in
+/// digit order.
+pub(crate) struct Descendants {
+    stack: Vec<Cell>,
+    res: u8,
+}
+
+impl Descendants {
+    /// Returns an iterator over `cell`'s res-`res` descendants.
+    ///
+    /// Yields nothing if `cell` is finer than `res`.
+    pub(crate) fn new(cell: Cell, res: u8) -> Self {
+        let stack = if cell.res() <= res {
+            vec![cell]
+        } else {
+            vec![]
+        };
+        Self { stack, res }
+    }
+}
+
+impl Iterator for Descendants {
+    type Item = Cell;
+
+    fn next(&mut self) -> Option<Cell> {
+        while let Some(cell) = self.stack.pop() {
+            if cell.res() == self.res {
+                return Some(cell);
+            }
+            let is_pentagon = cell.is_pentagon();
+            for digit in (0..7).rev().filter(|&digit| !(is_pentagon && digit == 1)) {
+                self.stack
+                    .push(cell.to_child(digit).expect("res < self.res <= 15"));
+            }
+        }
+        None
+    }
+}
+
 impl From<Cell> for CellStack {
     fn from(cell: Cell) -> CellStack {
         CellStack(Some(cell))
@@ -362,6 +481,53 @@ mod tests {
         assert_eq!(idx.digit(15), Some(7));
     }
 
+    #[test]
+    fn test_cell_from_raw() {
+        assert!(Cell::from_raw(0x85283473fffffff).is_ok());
+        assert!(Cell::from_raw(0x8001fffffffffff).is_ok());
+        assert!(matches!(Cell::from_raw(0), Err(Error::Index(0))));
+        // Digit 7 at res 3 and res 5 of an otherwise valid res-5 cell.
+        for raw in [0x85283773fffffff, 0x8528347ffffffff] {
+            assert!(matches!(Cell::from_raw(raw), Err(Error::Index(r)) if r == raw));
+        }
+    }
+
+    #[test]
+    fn test_cell_is_pentagon() {
+        let pentagon = Cell::from_base(4);
+        assert!(pentagon.is_pentagon());
+        assert!(pentagon.to_child(0).unwrap().is_pentagon());
+        assert!(!pentagon.to_child(2).unwrap().is_pentagon());
+        assert!(!Cell::from_base(20).is_pentagon());
+        assert!(!Cell::from_raw(0x85283473fffffff).unwrap().is_pentagon());
+    }
+
+    #[test]
+    fn test_descendants() {
+        let hexagon = Cell::from_raw(0x85283473fffffff).unwrap();
+        assert_eq!(Descendants::new(hexagon, 5).collect::<Vec<_>>(), [hexagon]);
+        assert_eq!(Descendants::new(hexagon, 4).count(), 0);
+        assert_eq!(Descendants::new(hexagon, 7).count(), 49);
+        assert!(Descendants::new(hexagon, 7)
+            .all(|cell| cell.res() == 7 && cell.to_parent(5) == Some(hexagon)));
+
+        // A pentagon has 1 pentagon and 5 hexagon children.
+        let pentagon = Cell::from_base(4);
+        assert_eq!(Descendants::new(pentagon, 1).count(), 6);
+        assert_eq!(Descendants::new(pentagon, 2).count(), 1 + 5 + 5 * 7);
+
+        for res in 0..8 {
+            assert_eq!(
+                pentagon.descendant_count(res),
+                Descendants::new(pentagon, res).count() as u64
+            );
+            assert_eq!(
+                hexagon.descendant_count(res),
+                Descendants::new(hexagon, res).count() as u64
+            );
+        }
+    }
+
     #[test]
     fn test_cell_to_parent() {
         let cell = Cell::from_raw(0x85283473fffffff).unwrap();
@@ -12,6 +12,9 @@ pub trait Compactor<V> {
     /// Given an intermediate (not-leaf) node's cell and up to 7
     /// children, you can choose to leave the node alone by returning
     /// `None`, or turn it into a leaf-node by return `Some(value)`.
+    ///
+    /// Note that [pentagon][Cell::is_pentagon] cells only have 6
+    /// children, so `children[1]` is always `None` for them.
     fn compact(&mut self, cell: Cell, children: [Option<&V>; 7]) -> Option<V>;
 }
 
@@ -32,8 +35,8 @@ impl<V> Compactor<V> for NullCompactor {
 pub struct SetCompactor;
 
 impl Compactor<()> for SetCompactor {
-    fn compact(&mut self, _cell: Cell, children: [Option<&()>; 7]) -> Option<()> {
-        if children.iter().all(Option::is_some) {
+    fn compact(&mut self, cell: Cell, children: [Option<&()>; 7]) -> Option<()> {
+        if existing_children(cell, &children).all(|child| child.is_some()) {
             Some(())
         } else {
             None
@@ -47,12 +50,27 @@ impl Compactor<()> for SetCompactor {
 pub struct EqCompactor;
 
 impl<V: PartialEq + Clone> Compactor<V> for EqCompactor {
-    fn compact(&mut self, _cell: Cell, children: [Option<&V>; 7]) -> Option<V> {
-        if let [Some(v0), Some(v1), Some(v2), Some(v3), Some(v4), Some(v5), Some(v6)] = children {
-            if v0 == v1 && v1 == v2 && v2 == v3 && v3 == v4 && v4 == v5 && v5 == v6 {
-                return Some(v0.clone());
-            }
-        };
-        None
+    fn compact(&mut self, cell: Cell, children: [Option<&V>; 7]) -> Option<V> {
+        let mut children = existing_children(cell, &children);
+        let first = children.next().flatten()?;
+        if children.all(|child| child == Some(first)) {
+            Some(first.clone())
+        } else {
+            None
+        }
     }
 }
+
+/// Returns an iterator over the children slots that exist for
+/// `cell`, skipping the missing digit-1 child of pentagons.
+fn existing_children<'a, V>(
+    cell: Cell,
+    children: &'a [Option<&'a V>; 7],
+) -> impl Iterator<Item = Option<&'a V>> + 'a {
+    let is_pentagon = cell.is_pentagon();
+    children
+        .iter()
+        .enumerate()
+        .filter(move |(digit, _)| !(is_pentagon && *digit == 1))
+        .map(|(_, child)| *child)
+}
@@ -0,0 +1,167 @@
+//! Joint traversal of two trees' coverage.
+
+use crate::{node::Node, Cell};
+
+/// The number of res-`res` cells covered by each of two trees'
+/// nodes.
+///
+/// A tree covers a res-`res` cell when it contains the cell or any
+/// part of it, so leaves finer than `res` count as their res-`res`
+/// parent.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Overlap {
+    /// Cells covered by both `a` and `b`.
+    pub(crate) both: u64,
+    /// Cells covered by `a` only.
+    pub(crate) only_a: u64,
+    /// Cells covered by `b` only.
+    pub(crate) only_b: u64,
+}
+
+impl std::ops::Add for Overlap {
+    type Output = Overlap;
+
+    fn add(self, rhs: Overlap) -> Overlap {
+        Overlap {
+            both: self.both + rhs.both,
+            only_a: self.only_a + rhs.only_a,
+            only_b: self.only_b + rhs.only_b,
+        }
+    }
+}
+
+/// Returns the number of res-`res` cells covered by `node`.
+pub(crate) fn coverage<V>(cell: Cell, node: &Node<V>, res: u8) -> u64 {
+    overlap::<V, V>(cell, Some(node), None, res).only_a
+}
+
+/// Returns the overlap at `res` of nodes `a` and `b`, both at `cell`.
+pub(crate) fn overlap<A, B>(
+    cell: Cell,
+    a: Option<&Node<A>>,
+    b: Option<&Node<B>>,
+    res: u8,
+) -> Overlap {
+    debug_assert!(res < 16);
+    let full = || cell.descendant_count(res);
+    match (a, b) {
+        (None, None) => Overlap::default(),
+        (Some(_), Some(_)) if cell.res() >= res => Overlap {
+            both: 1,
+            ..Overlap::default()
+        },
+        (Some(_), None) if cell.res() >= res => Overlap {
+            only_a: 1,
+            ..Overlap::default()
+        },
+        (None, Some(_)) if cell.res() >= res => Overlap {
+            only_b: 1,
+            ..Overlap::default()
+        },
+        (Some(Node::Leaf(_)), Some(Node::Leaf(_))) => Overlap {
+            both: full(),
+            ..Overlap::default()
+        },
+        (Some(Node::Leaf(_)), None) => Overlap {
+            only_a: full(),
+            ..Overlap::default()
+        },
+        (None, Some(Node::Leaf(_))) => Overlap {
+            only_b: full(),
+            ..Overlap::default()
+        },
+        (Some(Node::Leaf(_)), Some(b @ Node::Parent(_))) => {
+            let both = coverage(cell, b, res);
+            Overlap {
+                both,
+                only_a: full() - both,
+                only_b: 0,
+            }
+        }
+        (Some(a @ Node::Parent(_)), Some(Node::Leaf(_))) => {
+            let both = coverage(cell, a, res);
+            Overlap {
+                both,
+                only_a: 0,
+                only_b: full() - both,
+            }
+        }
+        (a, b) => {
+            let a_children = match a {
+                Some(Node::Parent(children)) => Some(children),
+                _ => None,
+            };
+            let b_children = match b {
+                Some(Node::Parent(children)) => Some(children),
+                _ => None,
+            };
+            (0..7)
+                .map(|digit| {
+                    let a_child = a_children.and_then(|children| children[digit].as_deref());
+                    let b_child = b_children.and_then(|children| children[digit].as_deref());
+                    if a_child.is_none() && b_child.is_none() {
+                        return Overlap::default();
+                    }
+                    let child_cell = cell
+                        .to_child(digit as u8)
+                        .expect("parents are never res 15");
+                    overlap(child_cell, a_child, b_child, res)
+                })
+                .fold(Overlap::default(), |acc, o| acc + o)
+        }
+    }
+}
+
+/// How much of a cell one tree covers, as seen during a joint
+/// traversal.
+enum Side<'a, V> {
+    Empty,
+    Full,
+    Parent(&'a [Option<Box<Node<V>>>; 7]),
+}
+
+impl<'a, V> Side<'a, V> {
+    fn new(node: Option<&'a Node<V>>) -> Self {
+        match node {
+            None => Side::Empty,
+            Some(Node::Leaf(_)) => Side::Full,
+            Some(Node::Parent(children)) => Side::Parent(children),
+        }
+    }
+
+    fn child(&self, digit: usize) -> Self {
+        match self {
+            Side::Empty => Side::Empty,
+            Side::Full => Side::Full,
+            Side::Parent(children) => Side::new(children[digit].as_deref()),
+        }
+    }
+}
+
+/// Returns the first cell, in iteration order, covered by exactly
+/// one of nodes `a` and `b`, both at `cell`.
+pub(crate) fn first_difference<A, B>(
+    cell: Cell,
+    a: Option<&Node<A>>,
+    b: Option<&Node<B>>,
+) -> Option<Cell> {
+    side_difference(cell, Side::new(a), Side::new(b))
+}
+
+fn side_difference<A, B>(cell: Cell, a: Side<'_, A>, b: Side<'_, B>) -> Option<Cell> {
+    match (&a, &b) {
+        (Side::Empty, Side::Empty) | (Side::Full, Side::Full) => None,
+        (Side::Empty, Side::Full) | (Side::Full, Side::Empty) => Some(cell),
+        _ => {
+            let is_pentagon = cell.is_pentagon();
+            (0..7)
+                .filter(|&digit| !(is_pentagon && digit == 1))
+                .find_map(|digit| {
+                    let child_cell = cell
+                        .to_child(digit as u8)
+                        .expect("parents are never res 15");
+                    side_difference(child_cell, a.child(digit), b.child(digit))
+                })
+        }
+    }
+}
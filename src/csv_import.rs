@@ -0,0 +1,87 @@
+//! Constructing sets from CSV coverage exports.
+
+use crate::{compaction::SetCompactor, Cell, Error, HexTreeMap, HexTreeSet, Result};
+use std::io::Read;
+
+impl HexTreeSet {
+    /// Constructs a set from CSV data where the column named
+    /// `column` contains H3 cell indices as hexadecimal strings, such
+    /// as `8c2a1072b59ffff`.
+    ///
+    /// The first row must be a header. All other columns are
+    /// ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the CSV is malformed, the header has no
+    /// column named `column`, or a row's field is not a valid H3
+    /// cell. Errors for a specific row include its line number.
+    pub fn from_csv_reader<R: Read>(reader: R, column: &str) -> Result<Self> {
+        let mut rdr = ::csv::Reader::from_reader(reader);
+        let col_idx = rdr
+            .headers()?
+            .iter()
+            .position(|name| name.trim() == column)
+            .ok_or_else(|| Error::CsvColumn(column.to_owned()))?;
+        let mut set = HexTreeMap::with_compactor(SetCompactor);
+        for record in rdr.records() {
+            let record = record?;
+            let line = record.position().map_or(0, |pos| pos.line());
+            let field = record.get(col_idx).unwrap_or_default();
+            let cell = parse_cell(field).ok_or_else(|| Error::CsvField(line, field.to_owned()))?;
+            set.insert(cell, ());
+        }
+        Ok(set)
+    }
+}
+
+fn parse_cell(field: &str) -> Option<Cell> {
+    let field = field.trim();
+    let digits = field
+        .strip_prefix("0x")
+        .or_else(|| field.strip_prefix("0X"))
+        .unwrap_or(field);
+    u64::from_str_radix(digits, 16)
+        .ok()
+        .and_then(|raw| Cell::from_raw(raw).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Cell, Error, HexTreeSet};
+
+    #[test]
+    fn test_from_csv_reader() {
+        let csv = "name,h3\n\
+                   a,835990fffffffff\n\
+                   b,0x835991fffffffff\n\
+                   c, 835992fffffffff \n";
+        let set = HexTreeSet::from_csv_reader(csv.as_bytes(), "h3").unwrap();
+        assert_eq!(set.len(), 3);
+        for raw in [0x835990fffffffff, 0x835991fffffffff, 0x835992fffffffff] {
+            assert!(set.contains(Cell::from_raw(raw).unwrap()));
+        }
+        assert!(!set.contains(Cell::from_raw(0x835993fffffffff).unwrap()));
+    }
+
+    #[test]
+    fn test_from_csv_reader_errors() {
+        let csv = "name,h3\na,835990fffffffff\n";
+        assert!(matches!(
+            HexTreeSet::from_csv_reader(csv.as_bytes(), "cell"),
+            Err(Error::CsvColumn(column)) if column == "cell"
+        ));
+
+        let csv = "name,h3\na,835990fffffffff\nb,not-a-cell\n";
+        assert!(matches!(
+            HexTreeSet::from_csv_reader(csv.as_bytes(), "h3"),
+            Err(Error::CsvField(3, value)) if value == "not-a-cell"
+        ));
+
+        let csv = "name,h3\na,835990fffffffff,extra\n";
+        assert!(matches!(
+            HexTreeSet::from_csv_reader(csv.as_bytes(), "h3"),
+            Err(Error::Csv(_))
+        ));
+    }
+}
@@ -226,6 +226,69 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_validate() {
+        use crate::{Cell, HexTreeMap};
+        let idx_bytes = include_bytes!("../../assets/monaco.res12.h3idx");
+        let rdr = &mut idx_bytes.as_slice();
+        let mut cells = Vec::new();
+        while let Ok(idx) = rdr.read_u64::<LE>() {
+            cells.push(Cell::from_raw(idx).unwrap());
+        }
+        let monaco: HexTreeMap<Cell> = cells.iter().copied().zip(cells.iter().copied()).collect();
+
+        let mut wtr = vec![];
+        monaco
+            .to_disktree(std::io::Cursor::new(&mut wtr), |wtr, val| {
+                bincode::serialize_into(wtr, val)
+            })
+            .unwrap();
+        let disktree = DiskTreeMap::with_buf(wtr.clone()).unwrap();
+        assert_eq!(disktree.validate().unwrap(), monaco.len());
+
+        let truncated = DiskTreeMap::with_buf(wtr[..wtr.len() - 1].to_vec()).unwrap();
+        assert!(truncated.validate().is_err());
+
+        // Point the first child of the first occupied base cell's node
+        // back at that node. The node is a tag byte followed by its
+        // child dptrs.
+        let base_dptr_pos = crate::disktree::tree::HDR_SZ
+            + dptr::Dp::size() * monaco.iter().next().unwrap().0.base() as usize;
+        let mut node_pos = [0u8; 8];
+        node_pos[..dptr::Dp::size()].copy_from_slice(&wtr[base_dptr_pos..][..dptr::Dp::size()]);
+        let node_pos = u64::from_le_bytes(node_pos);
+        let mut cyclic = wtr;
+        cyclic[node_pos as usize + 1..][..dptr::Dp::size()]
+            .copy_from_slice(&node_pos.to_le_bytes()[..dptr::Dp::size()]);
+        assert!(matches!(
+            DiskTreeMap::with_buf(cyclic).unwrap().validate(),
+            Err(crate::Error::Io(e)) if e.to_string() == "child precedes its parent"
+        ));
+
+        // Retag the sole res-15 leaf, the last byte written, as a
+        // parent with no children.
+        let mut res15: HexTreeMap<&[u8]> = HexTreeMap::new();
+        res15.insert(Cell::from_raw(0x8f2834734d10669).unwrap(), &[]);
+        let mut wtr = vec![];
+        res15
+            .to_disktree(std::io::Cursor::new(&mut wtr), |wtr, val| {
+                wtr.write_all(val)
+            })
+            .unwrap();
+        assert_eq!(
+            DiskTreeMap::with_buf(wtr.clone())
+                .unwrap()
+                .validate()
+                .unwrap(),
+            1
+        );
+        *wtr.last_mut().unwrap() = 0b1000_0000;
+        assert!(matches!(
+            DiskTreeMap::with_buf(wtr).unwrap().validate(),
+            Err(crate::Error::Io(e)) if e.to_string() == "res 15 node has children"
+        ));
+    }
+
     #[test]
     fn test_empty_disktree() {
         use crate::HexTreeMap;
@@ -115,8 +115,55 @@ impl DiskTreeMap {
         Iter::new((*self.0).as_ref())
     }
 
+    /// Walks the entire tree, checking that it is well formed, and
+    /// returns its leaf count.
+    ///
+    /// This is a cheap integrity check for a freshly opened file:
+    /// truncated or corrupted files are rejected here instead of
+    /// failing, or panicking, on some later lookup.
+    pub fn validate(&self) -> Result<usize> {
+        let mut csr = Cursor::new((*self.0).as_ref());
+        let mut leaves = 0;
+        for base in 0..122 {
+            csr.seek(SeekFrom::Start((HDR_SZ + Dp::size() * base) as u64))?;
+            let node_dptr = Dp::read(&mut csr)?;
+            if node_dptr.is_null() {
+                continue;
+            }
+            if node_dptr < Dp::from(HDR_SZ + Dp::size() * 122) {
+                return Err(corrupt("base cell points into header"));
+            }
+            leaves += Self::validate_node(&mut csr, 0, node_dptr)?;
+        }
+        Ok(leaves)
+    }
+
+    fn validate_node(csr: &mut Cursor<&[u8]>, res: u8, node_dptr: Dp) -> Result<usize> {
+        csr.seek(SeekFrom::Start(node_dptr.into()))?;
+        match Node::read(csr)? {
+            Node::Leaf(range) if range.end <= csr.get_ref().len() => Ok(1),
+            Node::Leaf(_) => Err(corrupt("leaf value extends past end of buffer")),
+            Node::Parent(_) if res == 15 => Err(corrupt("res 15 node has children")),
+            Node::Parent(children) => {
+                let mut leaves = 0;
+                for &child_dptr in children.iter().flatten() {
+                    // Children are always written after their parent.
+                    if child_dptr <= node_dptr {
+                        return Err(corrupt("child precedes its parent"));
+                    }
+                    leaves += Self::validate_node(csr, res + 1, child_dptr)?;
+                }
+                Ok(leaves)
+            }
+        }
+    }
+
     /// Returns the DPtr to a base (res0) cell dptr.
     fn base_cell_dptr(cell: Cell) -> Dp {
         Dp::from(HDR_SZ + Dp::size() * cell.base() as usize)
     }
 }
+
+fn corrupt(msg: &str) -> Error {
+    Error::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, msg))
+}
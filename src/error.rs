@@ -8,6 +8,9 @@ pub enum Error {
     /// An invalid raw source value was used for an H3 cell.
     Index(u64),
 
+    /// A patch is truncated or does not apply to the target set.
+    InvalidPatch,
+
     /// An io error.
     #[cfg(feature = "disktree")]
     Io(std::io::Error),
@@ -31,6 +34,18 @@ pub enum Error {
     /// User-provided serializer failed.
     #[cfg(feature = "disktree")]
     Writer(Box<dyn std::error::Error + Send + Sync>),
+
+    /// Malformed CSV data.
+    #[cfg(feature = "csv")]
+    Csv(csv::Error),
+
+    /// The requested CSV column is not in the header.
+    #[cfg(feature = "csv")]
+    CsvColumn(String),
+
+    /// A CSV field (line, value) is not a valid H3 cell string.
+    #[cfg(feature = "csv")]
+    CsvField(u64, String),
 }
 
 #[cfg(feature = "disktree")]
@@ -40,11 +55,20 @@ impl std::convert::From<std::io::Error> for Error {
     }
 }
 
+#[cfg(feature = "csv")]
+impl std::convert::From<csv::Error> for Error {
+    fn from(other: csv::Error) -> Self {
+        Error::Csv(other)
+    }
+}
+
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             Error::Index(_) => None,
 
+            Error::InvalidPatch => None,
+
             #[cfg(feature = "disktree")]
             Error::Io(inner) => inner.source(),
 
@@ -62,6 +86,15 @@ impl std::error::Error for Error {
 
             #[cfg(feature = "disktree")]
             Error::Writer(inner) => inner.source(),
+
+            #[cfg(feature = "csv")]
+            Error::Csv(inner) => inner.source(),
+
+            #[cfg(feature = "csv")]
+            Error::CsvColumn(_) => None,
+
+            #[cfg(feature = "csv")]
+            Error::CsvField(_, _) => None,
         }
     }
 }
@@ -71,6 +104,8 @@ impl std::fmt::Display for Error {
         match self {
             Error::Index(bits) => write!(f, "raw u64 is not a valid H3 index: {bits}"),
 
+            Error::InvalidPatch => write!(f, "patch is malformed or does not apply"),
+
             #[cfg(feature = "disktree")]
             Error::Io(io_error) => io_error.fmt(f),
 
@@ -98,6 +133,19 @@ impl std::fmt::Display for Error {
             Error::Writer(writer_error) => {
                 write!(f, "provided writer returned an error, got {writer_error}")
             }
+
+            #[cfg(feature = "csv")]
+            Error::Csv(csv_error) => csv_error.fmt(f),
+
+            #[cfg(feature = "csv")]
+            Error::CsvColumn(column) => {
+                write!(f, "CSV header has no column named {column:?}")
+            }
+
+            #[cfg(feature = "csv")]
+            Error::CsvField(line, value) => {
+                write!(f, "CSV line {line}: {value:?} is not a valid H3 cell")
+            }
         }
     }
 }
@@ -1,18 +1,27 @@
 //! A HexTreeMap is a structure for mapping geographical regions to values.
 
 pub use crate::entry::{Entry, OccupiedEntry, VacantEntry};
+pub use crate::node_ref::NodeRef;
 use crate::{
-    cell::CellStack,
+    cell::{CellStack, Descendants},
     compaction::{Compactor, NullCompactor},
+    coverage::{self, Overlap},
     digits::Digits,
     node::Node,
-    Cell,
+    Cell, Result,
 };
-use std::{cmp::PartialEq, iter::FromIterator};
+use std::{cmp::PartialEq, collections::BTreeMap, iter::FromIterator};
 
 /// A HexTreeMap is a structure for mapping geographical regions to
 /// values.
 ///
+/// # Thread safety
+///
+/// A `HexTreeMap` has no interior mutability, so it is `Send` and
+/// `Sync` whenever its value and compactor types are. Read-only
+/// methods such as [contains][Self::contains] and [get][Self::get]
+/// only take `&self`, so a map wrapped in an [Arc][std::sync::Arc]
+/// can be queried from many threads concurrently without locking.
 ///
 /// [serde]: https://docs.rs/serde/latest/serde/
 ///
@@ -61,13 +70,22 @@ use std::{cmp::PartialEq, iter::FromIterator};
 /// #     Ok(())
 /// # }
 /// ```
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HexTreeMap<V, C = NullCompactor> {
     /// All h3 0 base cell indices in the tree
     pub(crate) nodes: Box<[Option<Box<Node<V>>>]>,
     /// User-provided compator. Defaults to the null compactor.
     compactor: C,
+    /// Finest resolution stored in the tree. Defaults to 15. Neither
+    /// serialized nor compared, as it only affects future inserts.
+    #[cfg_attr(feature = "serde", serde(skip, default = "default_max_res"))]
+    max_res: u8,
+}
+
+#[cfg(feature = "serde")]
+fn default_max_res() -> u8 {
+    15
 }
 
 impl<V> HexTreeMap<V, NullCompactor> {
@@ -82,13 +100,24 @@ impl<V> HexTreeMap<V, NullCompactor> {
                 .take(122)
                 .collect::<Box<[Option<Box<Node<V>>>]>>(),
             compactor: NullCompactor,
+            max_res: 15,
         }
     }
 }
 
 impl<V, C: Compactor<V>> HexTreeMap<V, C> {
     /// Adds a cell/value pair to the set.
+    ///
+    /// Cells finer than the map's [maximum
+    /// resolution][Self::with_max_res] are promoted to it before
+    /// insertion.
+    ///
+    /// Cells may be inserted in any resolution order. Inserting a
+    /// cell replaces any of its previously inserted descendants, and
+    /// inserting a cell that is already covered by a previously
+    /// inserted (or coalesced) ancestor is a no-op.
     pub fn insert(&mut self, cell: Cell, value: V) {
+        let cell = cell.to_parent(self.max_res).unwrap_or(cell);
         let base_cell = cell.base();
         let digits = Digits::new(cell);
         match self.nodes[base_cell as usize].as_mut() {
@@ -100,6 +129,99 @@ impl<V, C: Compactor<V>> HexTreeMap<V, C> {
             }
         }
     }
+
+    /// Runs the compactor over every node in the tree, from the
+    /// finest resolution up.
+    ///
+    /// Compaction normally only happens along the path of an
+    /// [insert][Self::insert]. This method is useful after
+    /// [replace_compactor][Self::replace_compactor], or to collapse
+    /// branches that the current compactor would coalesce but that
+    /// were built without it. The built-in compactors only change the
+    /// tree's representation, never its coverage; a custom
+    /// [Compactor] changes coverage exactly as it would on insert.
+    pub fn compact(&mut self) {
+        for (base, node) in self.nodes.iter_mut().enumerate() {
+            if let Some(node) = node.as_deref_mut() {
+                node.compact(Cell::from_base(base as u8), &mut self.compactor);
+            }
+        }
+    }
+
+    /// Adds a cell/value pair to the set and returns `true` if it
+    /// added new coverage.
+    ///
+    /// Returns `false` if `cell`, after promotion to the [maximum
+    /// resolution][Self::with_max_res], was already fully
+    /// [covered][Self::covers] by the set before this call, in which
+    /// case the set's coverage is unchanged.
+    pub fn insert_reporting(&mut self, cell: Cell, value: V) -> bool {
+        let cell = cell.to_parent(self.max_res).unwrap_or(cell);
+        let was_covered = self.covers(cell);
+        self.insert(cell, value);
+        !was_covered
+    }
+
+    /// Adds every cell/value pair from `iter`, like
+    /// [extend][Extend::extend], and returns statistics about how
+    /// much new coverage they added.
+    pub fn extend_counting<I>(&mut self, iter: I) -> InsertStats
+    where
+        I: IntoIterator<Item = (Cell, V)>,
+    {
+        let mut stats = InsertStats::default();
+        for (cell, value) in iter {
+            stats.total += 1;
+            if !self.insert_reporting(cell, value) {
+                stats.already_covered += 1;
+                continue;
+            }
+            stats.new += 1;
+            let inserted = cell.to_parent(self.max_res).unwrap_or(cell);
+            if let Some((leaf, _)) = self.get(inserted) {
+                if leaf.res() < inserted.res() {
+                    stats.coalesced += 1;
+                }
+            }
+        }
+        stats
+    }
+}
+
+/// Ingest statistics returned by
+/// [extend_counting][HexTreeMap::extend_counting].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct InsertStats {
+    /// Number of cells inserted.
+    pub total: u64,
+    /// Cells which were not yet fully covered by the map.
+    pub new: u64,
+    /// Cells which the map already fully covered, e.g. duplicates or
+    /// descendants of earlier cells.
+    pub already_covered: u64,
+    /// New cells which were immediately coalesced into a coarser
+    /// cell.
+    pub coalesced: u64,
+}
+
+impl<V: Clone, C: Compactor<V> + Clone> HexTreeMap<V, C> {
+    /// Returns `(cells_removed, bytes_saved)`, what running
+    /// [compact][HexTreeMap::compact] would save, without modifying
+    /// the tree.
+    ///
+    /// Bytes are counted as in
+    /// [memory_by_resolution][HexTreeMap::memory_by_resolution].
+    /// The dry run compacts a clone of the tree, so it temporarily
+    /// needs as much memory again.
+    pub fn compaction_savings(&self) -> (usize, usize) {
+        let mut compacted = self.clone();
+        compacted.compact();
+        let bytes = |map: &Self| map.memory_by_resolution().values().sum::<usize>();
+        (
+            self.len() - compacted.len(),
+            bytes(self) - bytes(&compacted),
+        )
+    }
 }
 
 impl<V, C> HexTreeMap<V, C> {
@@ -114,6 +236,7 @@ impl<V, C> HexTreeMap<V, C> {
                 .take(122)
                 .collect::<Box<[Option<Box<Node<V>>>]>>(),
             compactor,
+            max_res: 15,
         }
     }
 
@@ -127,9 +250,29 @@ impl<V, C> HexTreeMap<V, C> {
         HexTreeMap {
             nodes: self.nodes,
             compactor: new_compactor,
+            max_res: self.max_res,
         }
     }
 
+    /// Limits the finest resolution of cells stored in the map,
+    /// consuming `self`.
+    ///
+    /// Any cell finer than `max_res` passed to [insert][Self::insert]
+    /// is replaced by its res-`max_res` parent, slightly over-covering
+    /// the inserted region in exchange for bounding the tree's depth.
+    /// Cells already in the map are left as-is.
+    ///
+    /// The limit is not serialized and does not affect equality, so
+    /// a deserialized map accepts cells down to resolution 15 again.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_res` is greater than 15.
+    pub fn with_max_res(self, max_res: u8) -> Self {
+        assert!(max_res <= 15, "H3 resolutions are in [0, 15]");
+        Self { max_res, ..self }
+    }
+
     /// Returns the number of H3 cells in the set.
     ///
     /// This method only considers complete, or leaf, cells in the
@@ -145,6 +288,83 @@ impl<V, C> HexTreeMap<V, C> {
         self.len() == 0
     }
 
+    /// Returns the number of cells in the set at each resolution.
+    ///
+    /// Only resolutions with at least one cell are present.
+    pub fn resolution_histogram(&self) -> BTreeMap<u8, usize> {
+        let mut histogram = BTreeMap::new();
+        for (cell, _) in self.iter() {
+            *histogram.entry(cell.res()).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    /// Returns the resolution at the `p`th percentile of cells in the
+    /// set, where `p` is in `[0, 1]`.
+    ///
+    /// Uses the nearest-rank method, so `p = 0.0` returns the
+    /// coarsest resolution present, `p = 1.0` the finest, and `p =
+    /// 0.5` the median. Returns `None` if the set is empty or `p` is
+    /// not in `[0, 1]`.
+    pub fn resolution_percentile(&self, p: f64) -> Option<u8> {
+        if !(0.0..=1.0).contains(&p) {
+            return None;
+        }
+        let histogram = self.resolution_histogram();
+        let len: usize = histogram.values().sum();
+        let rank = ((p * len as f64).ceil() as usize).max(1);
+        let mut seen = 0;
+        for (res, count) in histogram {
+            seen += count;
+            if seen >= rank {
+                return Some(res);
+            }
+        }
+        None
+    }
+
+    /// Returns the number of distinct resolutions among the tree's
+    /// cells.
+    ///
+    /// This is `1` for a tree of uniform resolution, and greater once
+    /// cells of mixed resolution were inserted or coalesced.
+    pub fn distinct_resolution_count(&self) -> usize {
+        self.resolution_histogram().len()
+    }
+
+    /// Returns the area-weighted mean resolution of the tree's
+    /// cells, or `None` if the tree is empty.
+    ///
+    /// Each cell is weighted by its nominal area, which shrinks by a
+    /// factor of seven per resolution, so coarse cells dominate. The
+    /// real variation of H3 cell areas is ignored.
+    pub fn mean_resolution_weighted(&self) -> Option<f64> {
+        let (weighted_sum, total_weight) = self
+            .iter()
+            .map(|(cell, _)| (cell.res(), 7_f64.powi(-i32::from(cell.res()))))
+            .fold((0.0, 0.0), |(sum, total), (res, weight)| {
+                (sum + f64::from(res) * weight, total + weight)
+            });
+        if total_weight > 0.0 {
+            Some(weighted_sum / total_weight)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the number of bytes used by the tree's nodes at each
+    /// resolution.
+    ///
+    /// Only node storage is counted. Neither the fixed table of 122
+    /// base cells nor any heap memory owned by values is included.
+    pub fn memory_by_resolution(&self) -> BTreeMap<u8, usize> {
+        let mut sizes = BTreeMap::new();
+        for node in self.nodes.iter().flatten() {
+            node.memory_by_resolution(0, &mut sizes);
+        }
+        sizes
+    }
+
     /// Returns `true` if the set fully contains `cell`.
     ///
     /// This method will return `true` if any of the following are
@@ -167,6 +387,115 @@ impl<V, C> HexTreeMap<V, C> {
         }
     }
 
+    /// Returns `true` if the set fully contains the cell with raw H3
+    /// index `raw`.
+    ///
+    /// This is [contains][Self::contains] for raw indices, such as
+    /// those streamed from a file, returning an error instead of
+    /// panicking or requiring a separate conversion when `raw` is
+    /// not a valid H3 cell.
+    pub fn contains_raw(&self, raw: u64) -> Result<bool> {
+        Cell::from_raw(raw).map(|cell| self.contains(cell))
+    }
+
+    /// Returns `true` if the set covers all of `cell`.
+    ///
+    /// Unlike [contains][Self::contains], this also returns `true`
+    /// when `cell` is covered by finer cells that were never
+    /// coalesced into it, as happens with [NullCompactor], or with
+    /// [EqCompactor][crate::compaction::EqCompactor] when sibling
+    /// values differ. Queries finer than every leaf covering them
+    /// behave exactly like `contains`.
+    pub fn covers(&self, cell: Cell) -> bool {
+        match self.get_raw(cell) {
+            Some((found, node)) => node.is_full(found),
+            None => false,
+        }
+    }
+
+    /// Returns an iterator which lazily yields, for each cell in
+    /// `cells`, `true` if the set fully contains it.
+    ///
+    /// This is equivalent to calling [contains][Self::contains] on
+    /// each cell, but without collecting the results.
+    pub fn contains_iter<'a, I>(&'a self, cells: I) -> impl Iterator<Item = bool> + 'a
+    where
+        I: IntoIterator<Item = Cell>,
+        I::IntoIter: 'a,
+    {
+        cells.into_iter().map(move |cell| self.contains(cell))
+    }
+
+    /// Returns the [Jaccard index] of the regions covered by `self`
+    /// and `other`, measured in res-`res` cells.
+    ///
+    /// A res-`res` cell is considered covered when a tree contains
+    /// the cell or any part of it. The result is in `[0, 1]`, where
+    /// `1.0` means both trees cover the same res-`res` cells. Two
+    /// empty trees are considered identical.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `res` is greater than 15.
+    ///
+    /// [Jaccard index]: https://en.wikipedia.org/wiki/Jaccard_index
+    pub fn jaccard<W, D>(&self, other: &HexTreeMap<W, D>, res: u8) -> f64 {
+        assert!(res <= 15, "H3 resolutions are in [0, 15]");
+        let overlap = self.overlap(other, res);
+        let union = overlap.both + overlap.only_a + overlap.only_b;
+        if union == 0 {
+            1.0
+        } else {
+            overlap.both as f64 / union as f64
+        }
+    }
+
+    /// Returns the number of res-`res` cells covered by `self` but
+    /// not by `baseline`.
+    ///
+    /// Coverage is measured as in [jaccard][Self::jaccard], in a
+    /// single joint traversal and without building the difference.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `res` is greater than 15.
+    pub fn added_count<W, D>(&self, baseline: &HexTreeMap<W, D>, res: u8) -> u64 {
+        assert!(res <= 15, "H3 resolutions are in [0, 15]");
+        self.overlap(baseline, res).only_a
+    }
+
+    /// Returns the first cell, in iteration order, covered by
+    /// exactly one of `self` and `other`, or `None` if both cover the
+    /// same region.
+    ///
+    /// The returned cell is the coarsest one where the trees diverge,
+    /// which pinpoints differences that a failing `assert_eq!` on
+    /// two large trees would bury. Only coverage is compared, not
+    /// values, and differently compacted trees covering the same
+    /// region do not differ.
+    pub fn first_difference<W, D>(&self, other: &HexTreeMap<W, D>) -> Option<Cell> {
+        self.nodes
+            .iter()
+            .zip(other.nodes.iter())
+            .enumerate()
+            .find_map(|(base, (a, b))| {
+                coverage::first_difference(Cell::from_base(base as u8), a.as_deref(), b.as_deref())
+            })
+    }
+
+    /// Jointly traverses `self` and `other` counting the res-`res`
+    /// cells covered by either.
+    pub(crate) fn overlap<W, D>(&self, other: &HexTreeMap<W, D>, res: u8) -> Overlap {
+        self.nodes
+            .iter()
+            .zip(other.nodes.iter())
+            .enumerate()
+            .map(|(base, (a, b))| {
+                coverage::overlap(Cell::from_base(base as u8), a.as_deref(), b.as_deref(), res)
+            })
+            .fold(Overlap::default(), |acc, overlap| acc + overlap)
+    }
+
     /// Returns a reference to the value corresponding to the given
     /// target cell or one of its parents.
     ///
@@ -180,6 +509,30 @@ impl<V, C> HexTreeMap<V, C> {
         }
     }
 
+    /// Returns the finest cell in the tree covering both `a` and `b`,
+    /// or `None` if either is not contained.
+    ///
+    /// This is the leaf containing both cells, if there is one,
+    /// otherwise the node where the paths to their leaves diverge.
+    /// Cells under different base cells have no common ancestor.
+    pub fn common_covered_ancestor(&self, a: Cell, b: Cell) -> Option<Cell> {
+        let (a_leaf, _) = self.get(a)?;
+        let (b_leaf, _) = self.get(b)?;
+        if a_leaf.base() != b_leaf.base() {
+            return None;
+        }
+        let mut ancestor = Cell::from_base(a_leaf.base());
+        for (a_digit, b_digit) in Digits::new(a_leaf).zip(Digits::new(b_leaf)) {
+            if a_digit != b_digit {
+                break;
+            }
+            ancestor = ancestor
+                .to_child(a_digit)
+                .expect("leaves are never finer than res 15");
+        }
+        Some(ancestor)
+    }
+
     #[inline]
     pub(crate) fn get_raw(&self, cell: Cell) -> Option<(Cell, &Node<V>)> {
         let base_cell = cell.base();
@@ -192,6 +545,51 @@ impl<V, C> HexTreeMap<V, C> {
         }
     }
 
+    /// Removes and returns the value of the leaf at precisely `cell`.
+    ///
+    /// Returns `None`, leaving the tree unchanged, if `cell` is not a
+    /// leaf.
+    pub(crate) fn remove_leaf(&mut self, cell: Cell) -> Option<V> {
+        let digits = Digits::new(cell);
+        Node::remove_leaf(&mut self.nodes[cell.base() as usize], digits)
+    }
+
+    /// Retains only the cell-value pairs for which `f` returns
+    /// `true`, removing the rest and pruning any subtrees left empty.
+    ///
+    /// `f` may also mutate the values it visits. Remaining cells are
+    /// not recompacted; call [HexTreeMap::compact] afterwards if `f`
+    /// can make sibling values compactable.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(Cell, &mut V) -> bool,
+    {
+        for (base, slot) in self.nodes.iter_mut().enumerate() {
+            Node::retain(slot, Cell::from_base(base as u8), &mut f);
+        }
+    }
+
+    /// Removes every region covered by `other` from this map.
+    ///
+    /// Leaves only partially covered by `other` are split into their
+    /// children, each keeping a clone of the leaf's value, until the
+    /// covered part can be removed exactly. Returns whether any
+    /// coverage was removed, so subtracting a disjoint map returns
+    /// `false` and leaves `self` unchanged.
+    pub fn subtract<W, D>(&mut self, other: &HexTreeMap<W, D>) -> bool
+    where
+        V: Clone,
+    {
+        let mut removed = false;
+        for (base, (slot, other_node)) in self.nodes.iter_mut().zip(other.nodes.iter()).enumerate()
+        {
+            if let Some(other_node) = other_node {
+                removed |= Node::subtract(slot, Cell::from_base(base as u8), other_node);
+            }
+        }
+        removed
+    }
+
     /// Returns a mutable reference to the value corresponding to the
     /// given target cell or one of its parents.
     ///
@@ -242,6 +640,199 @@ impl<V, C> HexTreeMap<V, C> {
         crate::iteration::IterMut::new(&mut self.nodes, CellStack::new())
     }
 
+    /// An iterator visiting all cell-value pairs expanded to the
+    /// uniform resolution `res`.
+    ///
+    /// This is H3's "uncompact" operation: every cell coarser than
+    /// `res` is replaced by all of its res-`res` descendants, each
+    /// paired with the coarser cell's value, and cells at `res` are
+    /// visited as-is. Cells finer than `res` can not be represented
+    /// at that resolution and are skipped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `res` is greater than 15.
+    pub fn uncompact(&self, res: u8) -> impl Iterator<Item = (Cell, &V)> {
+        assert!(res <= 15, "H3 resolutions are in [0, 15]");
+        self.iter()
+            .flat_map(move |(cell, val)| Descendants::new(cell, res).map(move |cell| (cell, val)))
+    }
+
+    /// An iterator visiting every node in the tree, intermediate
+    /// and leaf, in depth-first pre-order.
+    ///
+    /// Each parent is visited before its children, which follow in
+    /// digit order, so the leaves are visited in the same order as
+    /// [iter][Self::iter]. Use [NodeRef::res] for a node's depth and
+    /// [NodeRef::is_leaf] to tell leaves apart.
+    pub fn iter_nodes(&self) -> impl Iterator<Item = NodeRef<'_, V>> {
+        let mut stack: Vec<NodeRef<'_, V>> = self.roots().collect();
+        stack.reverse();
+        std::iter::from_fn(move || {
+            let node = stack.pop()?;
+            let first_child = stack.len();
+            stack.extend(node.children());
+            stack[first_child..].reverse();
+            Some(node)
+        })
+    }
+
+    /// Returns the fewest cells, none coarser than `res`, which
+    /// together cover exactly the same region as the tree.
+    ///
+    /// Leaves coarser than `res` are expanded to their res-`res`
+    /// descendants, and complete groups of finer siblings which were
+    /// never coalesced are replaced by their parent. Confirming each
+    /// returned cell, e.g. with remote `contains` calls, is the
+    /// cheapest way to prove the whole region is covered when
+    /// queries may not be coarser than `res`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `res` is greater than 15.
+    pub fn covering_cells(&self, res: u8) -> Vec<Cell> {
+        assert!(res <= 15, "H3 resolutions are in [0, 15]");
+        let mut cells = Vec::new();
+        for (base, node) in self.nodes.iter().enumerate() {
+            if let Some(node) = node {
+                node.covering_cells(Cell::from_base(base as u8), res, &mut cells);
+            }
+        }
+        cells
+    }
+
+    /// Returns the tree's cells as inclusive `(start, end)` runs of
+    /// raw H3 indices.
+    ///
+    /// A run holds cells of the same resolution whose indices are
+    /// adjacent, i.e. siblings with consecutive digits, so the
+    /// children of a hexagon collapse into a single run. At res 0 a
+    /// run is instead consecutive base cells. A pentagon
+    /// has no digit-1 child, so its children form two runs, one of
+    /// digit 0 alone and one of digits 2 through 6. Runs are returned
+    /// in [iteration order][Self::iter], which is not raw index order.
+    pub fn to_index_ranges(&self) -> Vec<(u64, u64)> {
+        let mut ranges: Vec<(u64, u64)> = Vec::new();
+        let mut run_res = None;
+        for (cell, _) in self.iter() {
+            let raw = cell.into_raw();
+            let step = 1 << (3 * (15 - cell.res() as u64));
+            match ranges.last_mut() {
+                Some((_, end)) if run_res == Some(cell.res()) && *end + step == raw => *end = raw,
+                _ => ranges.push((raw, raw)),
+            }
+            run_res = Some(cell.res());
+        }
+        ranges
+    }
+
+    /// Returns the tree's cells whose raw H3 indices fall in the
+    /// inclusive range `start..=end`, in [iteration order][Self::iter].
+    ///
+    /// Subtrees whose descendants' indices all fall outside the range
+    /// are skipped without being visited, making this suitable for
+    /// partitioning a tree by key range. Since the resolution is
+    /// stored above the base cell and digits, a range spanning
+    /// several resolutions covers every cell of the resolutions in
+    /// between.
+    pub fn leaves_in_index_range(&self, start: u64, end: u64) -> Vec<Cell> {
+        let range = start..=end;
+        let mut cells = Vec::new();
+        for (base, node) in self.nodes.iter().enumerate() {
+            if let Some(node) = node {
+                node.leaves_in_index_range(Cell::from_base(base as u8), &range, &mut cells);
+            }
+        }
+        cells
+    }
+
+    /// An iterator visiting the tree's occupied base (res-0) nodes.
+    ///
+    /// Use [NodeRef::children] to walk further down the tree.
+    pub fn roots(&self) -> impl Iterator<Item = NodeRef<'_, V>> {
+        self.nodes.iter().enumerate().filter_map(|(base, node)| {
+            node.as_deref()
+                .map(|node| NodeRef::new(Cell::from_base(base as u8), node))
+        })
+    }
+
+    /// An iterator visiting, in [iteration order][Self::iter], the
+    /// cell-value pairs which come after `after`.
+    ///
+    /// Cells related to `after`, i.e. `after` itself, its parent
+    /// leaf, or its descendants, are skipped. Resuming from the last
+    /// cell previously returned therefore continues where that
+    /// iteration left off, and only the path to `after` is walked
+    /// to find the starting point, which makes this suitable for
+    /// paginating over large trees.
+    pub fn iter_from(&self, after: Cell) -> impl Iterator<Item = (Cell, &V)> {
+        // Later siblings of each node on the path to `after`, from
+        // the deepest level up.
+        let mut levels: Vec<Vec<(Cell, &Node<V>)>> = Vec::new();
+        let mut cell = Cell::from_base(after.base());
+        let mut node = self.nodes[after.base() as usize].as_deref();
+        for digit in Digits::new(after) {
+            match node {
+                Some(Node::Parent(children)) => {
+                    levels.push(
+                        children
+                            .iter()
+                            .enumerate()
+                            .skip(digit as usize + 1)
+                            .filter_map(|(digit, child)| {
+                                let child_cell = cell
+                                    .to_child(digit as u8)
+                                    .expect("parents are never res 15");
+                                child.as_deref().map(|child| (child_cell, child))
+                            })
+                            .collect(),
+                    );
+                    node = children[digit as usize].as_deref();
+                    cell = cell.to_child(digit).expect("parents are never res 15");
+                }
+                _ => break,
+            }
+        }
+        let later_bases = self
+            .nodes
+            .iter()
+            .enumerate()
+            .skip(after.base() as usize + 1)
+            .filter_map(|(base, node)| {
+                node.as_deref()
+                    .map(|node| (Cell::from_base(base as u8), node))
+            });
+        levels
+            .into_iter()
+            .rev()
+            .flatten()
+            .chain(later_bases)
+            .flat_map(|(cell, node)| match node {
+                Node::Leaf(val) => Some((cell, val))
+                    .into_iter()
+                    .chain(crate::iteration::Iter::empty()),
+                Node::Parent(children) => None
+                    .into_iter()
+                    .chain(crate::iteration::Iter::new(children, CellStack::from(cell))),
+            })
+    }
+
+    /// Returns the base (res-0) cells occupied in both `self` and
+    /// `other`.
+    ///
+    /// Only these base cells can contribute to the trees'
+    /// intersection, so they are a cheap way to partition the work
+    /// of intersecting large trees.
+    pub fn shared_root_cells<W, D>(&self, other: &HexTreeMap<W, D>) -> Vec<Cell> {
+        self.nodes
+            .iter()
+            .zip(other.nodes.iter())
+            .enumerate()
+            .filter(|(_, (a, b))| a.is_some() && b.is_some())
+            .map(|(base, _)| Cell::from_base(base as u8))
+            .collect()
+    }
+
     /// An iterator visiting the specified cell or its children
     /// references to the values.
     pub fn subtree_iter(&self, cell: Cell) -> impl Iterator<Item = (Cell, &V)> {
@@ -285,6 +876,28 @@ impl<V, C> HexTreeMap<V, C> {
     }
 }
 
+#[cfg(feature = "rayon")]
+impl<V: Sync, C: Sync> HexTreeMap<V, C> {
+    /// Returns, for each cell in `cells`, `true` if the set fully
+    /// contains it.
+    ///
+    /// Queries are split across rayon's global thread pool. Results
+    /// are in the same order as `cells` and are identical to calling
+    /// [contains][Self::contains] on each cell.
+    pub fn par_contains(&self, cells: &[Cell]) -> Vec<bool> {
+        use rayon::prelude::*;
+        cells.par_iter().map(|&cell| self.contains(cell)).collect()
+    }
+}
+
+impl<V: PartialEq, C: PartialEq> PartialEq for HexTreeMap<V, C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.nodes == other.nodes && self.compactor == other.compactor
+    }
+}
+
+impl<V: Eq, C: Eq> Eq for HexTreeMap<V, C> {}
+
 impl<V: PartialEq> Default for HexTreeMap<V, NullCompactor> {
     fn default() -> Self {
         HexTreeMap::new()
@@ -469,6 +1082,21 @@ impl<V: std::fmt::Debug, C> std::fmt::Debug for HexTreeMap<V, C> {
 mod tests {
     use super::*;
 
+    /// A res-2 hexagon and its seven children.
+    fn family() -> (Cell, [Cell; 7]) {
+        let parent = Cell::from_raw(0x825997fffffffff).unwrap();
+        let mut children = [parent; 7];
+        for (digit, child) in (0..7).zip(children.iter_mut()) {
+            *child = parent.to_child(digit).unwrap();
+        }
+        (parent, children)
+    }
+
+    /// A res-5 cell unrelated to [`family`].
+    fn stranger() -> Cell {
+        Cell::from_raw(0x85283473fffffff).unwrap()
+    }
+
     #[test]
     fn map_is_send() {
         fn assert_send<T: Send>() {}
@@ -480,4 +1108,798 @@ mod tests {
         fn assert_sync<T: Sync>() {}
         assert_sync::<HexTreeMap<i32>>();
     }
+
+    #[test]
+    fn public_types_are_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<crate::HexTreeSet>();
+        assert_send_sync::<HexTreeMap<i32, crate::compaction::EqCompactor>>();
+        assert_send_sync::<NodeRef<'_, i32>>();
+    }
+
+    #[test]
+    fn test_concurrent_reads() {
+        use crate::HexTreeSet;
+        use std::{sync::Arc, thread};
+
+        let (_, children) = family();
+        let covered = children[..6].to_vec();
+        let uncovered = children[6];
+        let set: Arc<HexTreeSet> = Arc::new(covered.iter().collect());
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let set = Arc::clone(&set);
+                let covered = covered.clone();
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        assert!(covered.iter().all(|&cell| set.contains(cell)));
+                        assert!(!set.contains(uncovered));
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_insert_reporting() {
+        use crate::HexTreeSet;
+        let (parent, children) = family();
+        let mut set: HexTreeSet = std::iter::empty::<Cell>().collect();
+        for &child in children.iter() {
+            assert!(set.insert_reporting(child, ()));
+            assert!(!set.insert_reporting(child, ()));
+        }
+        // All children coalesced into `parent`, so any of their
+        // descendants are already covered.
+        assert_eq!(
+            set.iter().map(|(cell, _)| cell).collect::<Vec<_>>(),
+            [parent]
+        );
+        let grandchild = children[3].to_child(5).unwrap();
+        assert!(!set.insert_reporting(grandchild, ()));
+        assert!(!set.insert_reporting(parent, ()));
+        assert_eq!(set.len(), 1);
+
+        // Uncompacted children still cover their parent.
+        let mut map: HexTreeMap<usize> = HexTreeMap::new();
+        let mut eq_map = HexTreeMap::with_compactor(crate::compaction::EqCompactor);
+        for (digit, &child) in children.iter().enumerate() {
+            assert!(map.insert_reporting(child, digit));
+            assert!(eq_map.insert_reporting(child, digit));
+        }
+        assert_eq!(map.len(), 7);
+        assert_eq!(eq_map.len(), 7);
+        assert!(!map.insert_reporting(parent, 7));
+        assert!(!eq_map.insert_reporting(parent, 7));
+        assert!(map.insert_reporting(stranger(), 8));
+
+        // Cells are promoted to the max resolution before the check,
+        // even when finer leaves predate the limit.
+        let res15 = Cell::from_raw(0x8f2830828052d25).unwrap();
+        let mut map: HexTreeMap<()> = HexTreeMap::new();
+        map.insert(res15.to_parent(12).unwrap(), ());
+        let mut map = map.with_max_res(10);
+        assert!(map.insert_reporting(res15, ()));
+        assert_eq!(map.iter().next().unwrap().0, res15.to_parent(10).unwrap());
+        assert!(!map.insert_reporting(res15, ()));
+    }
+
+    #[test]
+    fn test_insert_resolution_order() {
+        let (ancestor, children) = family();
+        let child = children[2];
+        let grandchild = child.to_child(4).unwrap();
+        let cousin = ancestor.to_parent(1).unwrap().to_child(0).unwrap();
+        let orderings = [
+            [ancestor, child, grandchild],
+            [ancestor, grandchild, child],
+            [child, ancestor, grandchild],
+            [child, grandchild, ancestor],
+            [grandchild, ancestor, child],
+            [grandchild, child, ancestor],
+        ];
+        for ordering in orderings.iter() {
+            let mut map = HexTreeMap::new();
+            map.insert(cousin, cousin);
+            for &cell in ordering.iter() {
+                map.insert(cell, cell);
+            }
+            // Regardless of order, the ancestor and its value win.
+            assert_eq!(
+                map.iter().map(|(k, v)| (k, *v)).collect::<Vec<_>>(),
+                [(cousin, cousin), (ancestor, ancestor)],
+                "{ordering:?}"
+            );
+            assert_eq!(map.get(grandchild), Some((ancestor, &ancestor)));
+        }
+    }
+
+    #[test]
+    fn test_with_max_res() {
+        let res15 = Cell::from_raw(0x8f2830828052d25).unwrap();
+        let res10 = res15.to_parent(10).unwrap();
+        let mut map = HexTreeMap::new().with_max_res(10);
+        map.insert(res15, "over-covered");
+        assert_eq!(map.get(res15), Some((res10, &"over-covered")));
+        assert!(map.contains(res15));
+        assert_eq!(map.iter().next().unwrap().0.res(), 10);
+
+        // Coarser cells are unaffected.
+        let res5 = res15.to_parent(5).unwrap();
+        map.insert(res5, "coarse");
+        assert_eq!(map.iter().collect::<Vec<_>>(), [(res5, &"coarse")]);
+
+        // The limit is a setting, not part of the tree.
+        let mut unlimited = HexTreeMap::new();
+        unlimited.insert(res5, "coarse");
+        assert_eq!(map, unlimited);
+    }
+
+    #[test]
+    fn test_pentagon_compaction() {
+        use crate::{compaction::EqCompactor, HexTreeSet};
+        let pentagon = Cell::from_base(4);
+        let descendants: Vec<Cell> = Descendants::new(pentagon, 2).collect();
+        assert_eq!(descendants.len(), 41);
+
+        let set: HexTreeSet = descendants.iter().collect();
+        assert_eq!(
+            set.iter().map(|(cell, _)| cell).collect::<Vec<_>>(),
+            [pentagon]
+        );
+
+        let mut map = HexTreeMap::with_compactor(EqCompactor);
+        map.extend(descendants.iter().map(|&cell| (cell, 1)));
+        assert_eq!(map.iter().collect::<Vec<_>>(), [(pentagon, &1)]);
+
+        // One missing pentagon grandchild prevents coalescing all the
+        // way up.
+        let set: HexTreeSet = descendants[1..].iter().collect();
+        assert_eq!(set.len(), 5 + 5);
+    }
+
+    #[test]
+    fn test_hexagon_six_children_do_not_coalesce() {
+        use crate::compaction::{EqCompactor, SetCompactor};
+        let (parent, family) = family();
+        assert!(!parent.is_pentagon());
+        for missing in 0..7 {
+            let children: Vec<Cell> = family
+                .iter()
+                .copied()
+                .filter(|&child| child != family[missing])
+                .collect();
+
+            let mut set = HexTreeMap::with_compactor(SetCompactor);
+            set.extend(children.iter().map(|&cell| (cell, ())));
+            let mut map = HexTreeMap::with_compactor(EqCompactor);
+            map.extend(children.iter().map(|&cell| (cell, 1)));
+            set.compact();
+            map.compact();
+
+            assert_eq!(set.len(), 6);
+            assert_eq!(map.len(), 6);
+            assert!(!set.contains(parent));
+            assert!(!map.contains(parent));
+            assert!(!set.contains(family[missing]));
+        }
+    }
+
+    #[test]
+    fn test_compact() {
+        use crate::compaction::SetCompactor;
+        let (parent, children) = family();
+        // All 49 res-4 descendants of `parent`.
+        let cells: Vec<Cell> = children
+            .iter()
+            .flat_map(|child| (0..7).map(move |digit| child.to_child(digit).unwrap()))
+            .collect();
+        let map: HexTreeMap<()> = cells.iter().map(|&cell| (cell, ())).collect();
+        assert_eq!(map.len(), 49);
+
+        let mut set = map.replace_compactor(SetCompactor);
+        set.compact();
+        assert_eq!(
+            set.iter().map(|(cell, _)| cell).collect::<Vec<_>>(),
+            [parent]
+        );
+        assert!(cells.iter().all(|&cell| set.contains(cell)));
+
+        // Compacting an incomplete family changes nothing.
+        let mut partial: HexTreeMap<(), _> = cells[1..]
+            .iter()
+            .map(|&cell| (cell, ()))
+            .collect::<HexTreeMap<()>>()
+            .replace_compactor(SetCompactor);
+        partial.compact();
+        assert_eq!(partial.len(), 6 + 6);
+    }
+
+    #[test]
+    fn test_jaccard() {
+        use crate::HexTreeSet;
+        let (_, children) = family();
+        let family: HexTreeSet = children.iter().collect();
+        let one_child: HexTreeSet = children[..1].iter().collect();
+        let grandchild: HexTreeSet = std::iter::once(children[0].to_child(3).unwrap()).collect();
+        let stranger: HexTreeSet = std::iter::once(stranger()).collect();
+        let empty: HexTreeSet = std::iter::empty::<Cell>().collect();
+
+        assert_eq!(family.jaccard(&family, 5), 1.0);
+        assert_eq!(family.jaccard(&stranger, 5), 0.0);
+        assert_eq!(empty.jaccard(&empty, 5), 1.0);
+        assert_eq!(empty.jaccard(&family, 5), 0.0);
+        assert_eq!(family.jaccard(&one_child, 3), 1.0 / 7.0);
+        assert_eq!(one_child.jaccard(&family, 4), 7.0 / 49.0);
+        // Finer cells count as their res-`res` parent.
+        assert_eq!(family.jaccard(&grandchild, 3), 1.0 / 7.0);
+        assert_eq!(family.jaccard(&grandchild, 5), 7.0 / 343.0);
+        assert_eq!(family.jaccard(&grandchild, 1), 1.0);
+    }
+
+    #[test]
+    fn test_resolution_percentile() {
+        let (parent, _) = family();
+        let child = |digits: &[u8]| {
+            digits
+                .iter()
+                .fold(parent, |cell, &digit| cell.to_child(digit).unwrap())
+        };
+        let mut map: HexTreeMap<()> = HexTreeMap::new();
+        assert_eq!(map.resolution_percentile(0.5), None);
+        for digits in [&[0][..], &[1], &[2, 0], &[3, 0, 0], &[3, 0, 1], &[3, 0, 2]].iter() {
+            map.insert(child(digits), ());
+        }
+        assert_eq!(
+            map.resolution_histogram().into_iter().collect::<Vec<_>>(),
+            [(3, 2), (4, 1), (5, 3)]
+        );
+        assert_eq!(map.resolution_percentile(0.0), Some(3));
+        assert_eq!(map.resolution_percentile(0.33), Some(3));
+        assert_eq!(map.resolution_percentile(0.5), Some(4));
+        assert_eq!(map.resolution_percentile(0.51), Some(5));
+        assert_eq!(map.resolution_percentile(1.0), Some(5));
+        assert_eq!(map.resolution_percentile(1.5), None);
+        assert_eq!(map.resolution_percentile(f64::NAN), None);
+    }
+
+    #[test]
+    fn test_retain() {
+        let (_, children) = family();
+        let other = stranger();
+        let mut map: HexTreeMap<u32> = HexTreeMap::new();
+        for (digit, &child) in (0..).zip(children.iter()) {
+            map.insert(child, digit);
+            map.insert(child.to_child(0).unwrap(), digit);
+        }
+        map.insert(other, 1);
+        assert_eq!(map.len(), 8);
+        assert_eq!(map.roots().count(), 2);
+
+        // Drop everything below the threshold, decrementing the rest.
+        map.retain(|_, value| {
+            *value = value.saturating_sub(1);
+            *value >= 3
+        });
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.roots().count(), 1);
+        assert!(!map.contains(other));
+        for (digit, &child) in children.iter().enumerate() {
+            assert_eq!(map.contains(child), digit >= 4);
+        }
+        assert_eq!(
+            map.iter().map(|(_, &value)| value).collect::<Vec<_>>(),
+            [3, 4, 5]
+        );
+
+        map.retain(|_, _| false);
+        assert!(map.is_empty());
+        assert_eq!(map.roots().count(), 0);
+    }
+
+    #[test]
+    fn test_subtract() {
+        let (parent, children) = family();
+        let grandchild = children[3].to_child(5).unwrap();
+        let other = stranger();
+
+        let mut map: HexTreeMap<u8> = HexTreeMap::new();
+        map.insert(parent, 7);
+        let unchanged = map.clone();
+        assert!(!map.subtract(&[(other, ())].iter().copied().collect::<HexTreeMap<()>>()));
+        assert_eq!(map, unchanged);
+
+        // Removing a grandchild splits the parent and its child.
+        let mut grandchild_map: HexTreeMap<()> = HexTreeMap::new();
+        grandchild_map.insert(grandchild, ());
+        assert!(map.subtract(&grandchild_map));
+        assert_eq!(map.len(), 12);
+        assert!(!map.contains(grandchild));
+        assert!(!map.contains(parent));
+        assert!(map.contains(children[3].to_child(4).unwrap()));
+        assert!(map.contains(children[6]));
+        assert!(map.iter().all(|(_, &value)| value == 7));
+        assert!(!map.subtract(&grandchild_map));
+
+        // Subtracting a coarser cell removes everything beneath it.
+        let mut parent_map: HexTreeMap<()> = HexTreeMap::new();
+        parent_map.insert(parent, ());
+        assert!(map.subtract(&parent_map));
+        assert!(map.is_empty());
+        assert_eq!(map.roots().count(), 0);
+    }
+
+    #[test]
+    fn test_subtract_pentagon() {
+        let pentagon = Cell::from_raw(0x8009fffffffffff).unwrap();
+        let mut map: HexTreeMap<()> = HexTreeMap::new();
+        map.insert(pentagon, ());
+        let mut hole: HexTreeMap<()> = HexTreeMap::new();
+        hole.insert(pentagon.to_child(2).unwrap(), ());
+        assert!(map.subtract(&hole));
+        assert_eq!(map.len(), 5);
+        assert!(map.iter().all(|(cell, _)| cell.res() == 1));
+    }
+
+    #[test]
+    fn test_covers() {
+        let (parent, children) = family();
+        let mut map: HexTreeMap<()> = children.iter().map(|&cell| (cell, ())).collect();
+        assert_eq!(map.len(), 7);
+        assert!(!map.contains(parent));
+        assert!(map.covers(parent));
+        assert!(map.covers(children[4]));
+        assert!(map.covers(children[4].to_child(2).unwrap()));
+        assert!(!map.covers(parent.to_parent(1).unwrap()));
+        assert!(!map.covers(stranger()));
+
+        map.remove_leaf(children[6]);
+        assert!(!map.covers(parent));
+        assert!(map.covers(children[5]));
+        assert!(!map.covers(children[6].to_child(0).unwrap()));
+
+        // Pentagons are covered by their six children.
+        let pentagon = Cell::from_raw(0x8009fffffffffff).unwrap();
+        let mut map: HexTreeMap<()> = HexTreeMap::new();
+        for digit in [0, 2, 3, 4, 5, 6].iter() {
+            map.insert(pentagon.to_child(*digit).unwrap(), ());
+        }
+        assert!(map.covers(pentagon));
+    }
+
+    #[test]
+    fn test_mean_resolution_weighted() {
+        let mut map: HexTreeMap<()> = HexTreeMap::new();
+        assert_eq!(map.mean_resolution_weighted(), None);
+
+        let (res2, _) = family();
+        map.insert(res2, ());
+        assert_eq!(map.mean_resolution_weighted(), Some(2.0));
+
+        // One res-2 cell has seven times the weight of one res-3 cell:
+        // (7 * 2 + 1 * 3) / 8.
+        let res3 = stranger().to_parent(3).unwrap();
+        map.insert(res3, ());
+        let mean = map.mean_resolution_weighted().unwrap();
+        assert!((mean - 17.0 / 8.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_first_difference() {
+        let (parent, children) = family();
+        let mut a: HexTreeMap<()> = HexTreeMap::new();
+        let mut b: HexTreeMap<u8> = HexTreeMap::new();
+        assert_eq!(a.first_difference(&b), None);
+
+        // Same coverage, one compacted and one not.
+        a.insert(parent, ());
+        b.extend(
+            (0..)
+                .zip(children.iter())
+                .map(|(digit, &cell)| (cell, digit)),
+        );
+        assert_eq!(a.first_difference(&b), None);
+        assert_eq!(b.first_difference(&a), None);
+
+        let missing = children[4].to_child(2).unwrap();
+        a.subtract(&[(missing, ())].iter().copied().collect::<HexTreeMap<()>>());
+        assert_eq!(a.first_difference(&b), Some(missing));
+        assert_eq!(b.first_difference(&a), Some(missing));
+
+        let extra = stranger();
+        a.insert(missing, ());
+        a.insert(extra, ());
+        assert_eq!(a.first_difference(&b), Some(extra));
+    }
+
+    #[test]
+    fn test_added_count() {
+        let (parent, children) = family();
+        let mut coverage: HexTreeMap<()> = HexTreeMap::new();
+        coverage.insert(parent, ());
+        coverage.insert(stranger(), ());
+        let mut baseline: HexTreeMap<()> = HexTreeMap::new();
+        baseline.insert(children[2], ());
+        baseline.insert(children[5].to_child(0).unwrap(), ());
+
+        let empty: HexTreeMap<()> = HexTreeMap::new();
+        for res in 5..8 {
+            let mut difference = coverage.clone();
+            difference.subtract(&baseline);
+            assert_eq!(
+                coverage.added_count(&baseline, res),
+                difference.added_count(&empty, res)
+            );
+            assert_eq!(
+                coverage.added_count(&baseline, res),
+                difference.uncompact(res).count() as u64
+            );
+        }
+        assert_eq!(coverage.added_count(&baseline, 3), 5 + 1);
+        assert_eq!(baseline.added_count(&coverage, 7), 0);
+    }
+
+    #[test]
+    fn test_covering_cells() {
+        let (parent, children) = family();
+        let other = stranger();
+        let mut map: HexTreeMap<()> = children.iter().map(|&cell| (cell, ())).collect();
+        map.insert(other, ());
+        assert_eq!(map.covering_cells(0), [other, parent]);
+        assert_eq!(map.covering_cells(2), [other, parent]);
+        assert_eq!(map.covering_cells(3).len(), 8);
+
+        let res4 = map.covering_cells(4);
+        assert_eq!(res4.len(), 49 + 1);
+        assert!(res4.iter().all(|cell| cell.res() >= 4));
+        assert_eq!(
+            res4.iter()
+                .map(|&cell| (cell, ()))
+                .collect::<HexTreeMap<()>>()
+                .first_difference(&map),
+            None
+        );
+    }
+
+    #[test]
+    fn test_iter_from() {
+        let (parent, children) = family();
+        let mut map: HexTreeMap<u32> = HexTreeMap::new();
+        for (digit, &child) in (0..).zip(children.iter()) {
+            map.insert(child.to_child(6 - digit).unwrap(), u32::from(digit));
+            map.insert(child.to_child(6).unwrap().to_child(digit).unwrap(), 7);
+        }
+        map.insert(stranger(), 8);
+        map.insert(Cell::from_raw(0x8f2834734d10669).unwrap(), 9);
+        let all: Vec<_> = map.iter().collect();
+
+        for page_size in 1..=all.len() {
+            let mut pages = map.iter().take(page_size).collect::<Vec<_>>();
+            while let Some(&(last, _)) = pages.last() {
+                let page: Vec<_> = map.iter_from(last).take(page_size).collect();
+                if page.is_empty() {
+                    break;
+                }
+                pages.extend(page);
+            }
+            assert_eq!(pages, all);
+        }
+
+        // Cursors need not be in the tree, and related cells are skipped.
+        assert_eq!(map.iter_from(parent).count(), 0);
+        assert_eq!(
+            map.iter_from(children[3]).collect::<Vec<_>>(),
+            all.iter()
+                .copied()
+                .filter(|(cell, _)| {
+                    cell.base() == parent.base()
+                        && Digits::new(cell.to_parent(3).unwrap()).last() > Some(3)
+                })
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_contains_raw() {
+        let mut map: HexTreeMap<()> = HexTreeMap::new();
+        map.insert(stranger(), ());
+        assert!(map.contains_raw(stranger().into_raw()).unwrap());
+        assert!(map.contains_raw(0x8f2834734d10669).unwrap());
+        assert!(!map.contains_raw(family().0.into_raw()).unwrap());
+        assert!(matches!(map.contains_raw(0), Err(crate::Error::Index(0))));
+
+        // Digit 7 at res 3 must be rejected, not walked into the tree.
+        let digit_7 = 0x85283773fffffff;
+        assert!(matches!(
+            map.contains_raw(digit_7),
+            Err(crate::Error::Index(raw)) if raw == digit_7
+        ));
+    }
+
+    #[test]
+    fn test_distinct_resolution_count() {
+        let (_, children) = family();
+        let mut set = HexTreeMap::with_compactor(crate::compaction::SetCompactor);
+        assert_eq!(set.distinct_resolution_count(), 0);
+        for child in children[..6].iter() {
+            set.insert(child.to_child(0).unwrap(), ());
+        }
+        assert_eq!(set.distinct_resolution_count(), 1);
+
+        // Completing res-3 child 0 coalesces it, mixing resolutions.
+        for digit in 1..7 {
+            set.insert(children[0].to_child(digit).unwrap(), ());
+        }
+        assert_eq!(set.distinct_resolution_count(), 2);
+    }
+
+    #[test]
+    fn test_extend_counting() {
+        let (parent, children) = family();
+        let child = |digit: usize| children[digit];
+        let cells = [
+            child(0),
+            child(1),
+            child(1),
+            child(1).to_child(3).unwrap(),
+            child(2),
+            child(3),
+            child(4),
+            child(5),
+            child(6),
+            child(6).to_child(0).unwrap(),
+        ];
+        let mut set = HexTreeMap::with_compactor(crate::compaction::SetCompactor);
+        let stats = set.extend_counting(cells.iter().map(|&cell| (cell, ())));
+        assert_eq!(
+            stats,
+            InsertStats {
+                total: 10,
+                new: 7,
+                already_covered: 3,
+                coalesced: 1,
+            }
+        );
+        assert_eq!(set.len(), 1);
+
+        // Without compaction the children stay separate, but still
+        // cover their parent.
+        let mut map: HexTreeMap<()> = HexTreeMap::new();
+        let stats = map.extend_counting(cells.iter().map(|&cell| (cell, ())));
+        assert_eq!(
+            stats,
+            InsertStats {
+                total: 10,
+                new: 7,
+                already_covered: 3,
+                coalesced: 0,
+            }
+        );
+        assert_eq!(map.len(), 7);
+        assert_eq!(
+            map.extend_counting(std::iter::once((parent, ()))),
+            InsertStats {
+                total: 1,
+                new: 0,
+                already_covered: 1,
+                coalesced: 0,
+            }
+        );
+
+        // A finer leaf from before `with_max_res` does not cover the
+        // promoted cell.
+        let res15 = Cell::from_raw(0x8f2830828052d25).unwrap();
+        let mut map: HexTreeMap<()> = HexTreeMap::new();
+        map.insert(res15.to_parent(12).unwrap(), ());
+        let mut map = map.with_max_res(10);
+        assert_eq!(
+            map.extend_counting(std::iter::once((res15, ()))),
+            InsertStats {
+                total: 1,
+                new: 1,
+                already_covered: 0,
+                coalesced: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_common_covered_ancestor() {
+        let (parent, children) = family();
+        let child = |digit: usize| children[digit];
+        let other = stranger();
+        let mut map: HexTreeMap<()> = HexTreeMap::new();
+        map.insert(child(0), ());
+        map.insert(child(1).to_child(4).unwrap(), ());
+        map.insert(other, ());
+
+        let a = child(0).to_child(2).unwrap();
+        let b = child(0).to_child(5).unwrap().to_child(1).unwrap();
+        assert_eq!(map.common_covered_ancestor(a, b), Some(child(0)));
+        assert_eq!(
+            map.common_covered_ancestor(a, child(1).to_child(4).unwrap()),
+            Some(parent)
+        );
+        assert_eq!(map.common_covered_ancestor(a, a), Some(child(0)));
+        assert_eq!(
+            map.common_covered_ancestor(a, parent.to_parent(0).unwrap()),
+            None
+        );
+        assert_eq!(map.common_covered_ancestor(a, child(2)), None);
+        assert_eq!(map.common_covered_ancestor(a, other), None);
+
+        // Cells under the same base cell share at least that cell.
+        let base = parent.to_parent(0).unwrap();
+        let parent_digit = Digits::new(parent).next().unwrap();
+        let far = base.to_child((parent_digit + 1) % 7).unwrap();
+        map.insert(far, ());
+        assert_eq!(map.common_covered_ancestor(a, far), Some(base));
+    }
+
+    #[test]
+    fn test_to_index_ranges() {
+        let (_, children) = family();
+        let child = |digit: usize| children[digit];
+        let mut map: HexTreeMap<()> = HexTreeMap::new();
+        assert!(map.to_index_ranges().is_empty());
+        map.extend(children.iter().map(|&cell| (cell, ())));
+        assert_eq!(
+            map.to_index_ranges(),
+            [(child(0).into_raw(), child(6).into_raw())]
+        );
+
+        // A hole, or a run of finer cells, splits the run.
+        map.remove_leaf(child(3));
+        map.insert(child(3).to_child(0).unwrap(), ());
+        map.insert(child(3).to_child(1).unwrap(), ());
+        assert_eq!(
+            map.to_index_ranges(),
+            [
+                (child(0).into_raw(), child(2).into_raw()),
+                (
+                    child(3).to_child(0).unwrap().into_raw(),
+                    child(3).to_child(1).unwrap().into_raw()
+                ),
+                (child(4).into_raw(), child(6).into_raw()),
+            ]
+        );
+
+        // A pentagon's missing digit-1 child splits its children.
+        let pentagon = Cell::from_raw(0x8009fffffffffff).unwrap();
+        let pentagon_child = |digit| pentagon.to_child(digit).unwrap();
+        let map: HexTreeMap<()> = [0, 2, 3, 4, 5, 6]
+            .iter()
+            .map(|&digit| (pentagon_child(digit), ()))
+            .collect();
+        assert_eq!(
+            map.to_index_ranges(),
+            [
+                (pentagon_child(0).into_raw(), pentagon_child(0).into_raw()),
+                (pentagon_child(2).into_raw(), pentagon_child(6).into_raw()),
+            ]
+        );
+
+        // Consecutive base cells form a single res-0 run.
+        let map: HexTreeMap<()> = [20, 21]
+            .iter()
+            .map(|&base| (Cell::from_base(base), ()))
+            .collect();
+        assert_eq!(
+            map.to_index_ranges(),
+            [(0x8029fffffffffff, 0x802bfffffffffff)]
+        );
+    }
+
+    #[test]
+    fn test_leaves_in_index_range() {
+        let (_, children) = family();
+        let child = |digit: usize| children[digit];
+        let mut map: HexTreeMap<()> = children.iter().map(|&cell| (cell, ())).collect();
+        map.insert(stranger(), ());
+        map.insert(Cell::from_raw(0x8f2834734d10669).unwrap(), ());
+
+        let naive = |start: u64, end: u64| {
+            map.iter()
+                .map(|(cell, _)| cell)
+                .filter(|cell| (start..=end).contains(&cell.into_raw()))
+                .collect::<Vec<_>>()
+        };
+        let bounds = [
+            0,
+            child(0).into_raw(),
+            child(2).into_raw(),
+            child(2).into_raw() + 1,
+            child(6).into_raw(),
+            stranger().into_raw(),
+            u64::MAX,
+        ];
+        for &start in bounds.iter() {
+            for &end in bounds.iter() {
+                assert_eq!(map.leaves_in_index_range(start, end), naive(start, end));
+            }
+        }
+        assert_eq!(
+            map.leaves_in_index_range(child(1).into_raw(), child(4).into_raw()),
+            [child(1), child(2), child(3), child(4)]
+        );
+    }
+
+    #[test]
+    fn test_compaction_savings() {
+        let (_, children) = family();
+        let map: HexTreeMap<()> = children.iter().map(|&cell| (cell, ())).collect();
+        assert_eq!(map.compaction_savings(), (0, 0));
+
+        let mut set = map.replace_compactor(crate::compaction::SetCompactor);
+        let before = set.clone();
+        let node_size = std::mem::size_of::<Node<()>>();
+        assert_eq!(set.compaction_savings(), (6, 7 * node_size));
+        assert_eq!(set, before);
+        set.compact();
+        assert_eq!(set.len(), 1);
+        assert_eq!(set.compaction_savings(), (0, 0));
+    }
+
+    #[test]
+    fn test_iter_nodes() {
+        fn count_nodes(node: NodeRef<'_, u8>) -> usize {
+            1 + node.children().map(count_nodes).sum::<usize>()
+        }
+
+        let (_, children) = family();
+        let mut map: HexTreeMap<u8> = HexTreeMap::new();
+        for &digit in [0, 3, 6].iter() {
+            map.insert(children[usize::from(digit)], digit);
+        }
+        map.insert(children[4].to_child(2).unwrap(), 42);
+        map.insert(stranger(), 5);
+
+        let nodes: Vec<_> = map.iter_nodes().collect();
+        assert_eq!(nodes.len(), map.roots().map(count_nodes).sum::<usize>());
+        // Per branch: base, res-1 and res-2 parents. Plus the res-3
+        // parent above 42, and the res-3 and res-4 parents above 5.
+        assert_eq!(nodes.len(), 2 * 3 + 1 + 2 + map.len());
+        assert_eq!(
+            nodes
+                .iter()
+                .filter(|node| node.is_leaf())
+                .map(|node| (node.cell(), node.value().unwrap()))
+                .collect::<Vec<_>>(),
+            map.iter().collect::<Vec<_>>()
+        );
+        for pair in nodes.windows(2) {
+            if pair[1].res() > pair[0].res() {
+                assert_eq!(pair[1].res(), pair[0].res() + 1);
+                assert_eq!(
+                    pair[1].cell().to_parent(pair[0].res()),
+                    Some(pair[0].cell())
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_memory_by_resolution() {
+        let children = [
+            Cell::from_raw(0x835990fffffffff).unwrap(),
+            Cell::from_raw(0x835991fffffffff).unwrap(),
+            Cell::from_raw(0x835992fffffffff).unwrap(),
+            Cell::from_raw(0x835993fffffffff).unwrap(),
+            Cell::from_raw(0x835994fffffffff).unwrap(),
+            Cell::from_raw(0x835995fffffffff).unwrap(),
+            Cell::from_raw(0x835996fffffffff).unwrap(),
+        ];
+        let map: HexTreeMap<i32> = children.iter().map(|&cell| (cell, 0)).collect();
+        let node_sz = std::mem::size_of::<Node<i32>>();
+        let sizes = map.memory_by_resolution();
+        assert_eq!(
+            sizes.into_iter().collect::<Vec<_>>(),
+            vec![(0, node_sz), (1, node_sz), (2, node_sz), (3, 7 * node_sz)]
+        );
+        assert!(HexTreeMap::<i32>::new().memory_by_resolution().is_empty());
+    }
 }
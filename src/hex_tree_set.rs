@@ -1,5 +1,11 @@
-use crate::{compaction::SetCompactor, Cell, HexTreeMap};
-use std::iter::FromIterator;
+use crate::{compaction::SetCompactor, node::Node, Cell, Error, HexTreeMap, Result};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashSet},
+    convert::TryInto,
+    io::Write,
+    iter::FromIterator,
+};
 
 /// A HexTreeSet is a structure for representing geographical regions
 /// and efficiently testing performing hit-tests on that region. Or,
@@ -71,3 +77,233 @@ impl<'a> FromIterator<&'a Cell> for HexTreeSet {
         set
     }
 }
+
+impl From<Vec<Cell>> for HexTreeSet {
+    fn from(cells: Vec<Cell>) -> Self {
+        cells.into_iter().collect()
+    }
+}
+
+impl From<&[Cell]> for HexTreeSet {
+    fn from(cells: &[Cell]) -> Self {
+        cells.iter().collect()
+    }
+}
+
+impl HexTreeSet {
+    /// Returns a copy of this set coarsened to at most `target_cells`
+    /// cells.
+    ///
+    /// Groups of sibling cells, complete or not, are repeatedly
+    /// replaced by their parent cell, each time choosing the group
+    /// whose replacement adds the least area. The result is therefore
+    /// a superset of `self` which over-covers the original region.
+    ///
+    /// Coarsening can not go past res-0, so the result may still
+    /// have more than `target_cells` cells if `self` spans more base
+    /// cells than that.
+    pub fn simplify_to(&self, target_cells: usize) -> HexTreeSet {
+        let mut simplified = self.clone();
+        let mut len = simplified.len();
+        let mut candidates = BinaryHeap::new();
+        for (base, node) in simplified.nodes.iter().enumerate() {
+            if let Some(node) = node.as_deref() {
+                find_coarsening_candidates(Cell::from_base(base as u8), node, &mut candidates);
+            }
+        }
+        while len > target_cells {
+            let cell = match candidates.pop() {
+                Some(Reverse((_cost, raw))) => Cell(raw),
+                None => break,
+            };
+            let (_, node) = simplified
+                .get_raw_mut(cell)
+                .expect("candidates are always in the tree");
+            len -= child_count(node) - 1;
+            *node = Node::Leaf(());
+
+            // The new leaf may complete its sibling group, which must
+            // then coalesce just as it would on insert.
+            let mut top = cell;
+            while let Some(parent) = top.res().checked_sub(1).and_then(|res| top.to_parent(res)) {
+                let (_, parent_node) = simplified
+                    .get_raw_mut(parent)
+                    .expect("parent of a node is always in the tree");
+                let n_children = child_count(parent_node);
+                parent_node.coalesce(parent, &mut SetCompactor);
+                if let Node::Parent(_) = parent_node {
+                    if let Some(cost) = coarsening_cost(parent, parent_node) {
+                        candidates.push(Reverse((cost, parent.into_raw())));
+                    }
+                    break;
+                }
+                len -= n_children - 1;
+                top = parent;
+            }
+        }
+        simplified
+    }
+
+    /// Builds a set from `cells` while also writing each cell's raw
+    /// index, as a little-endian `u64`, to `writer`.
+    ///
+    /// This writes the same `.h3idx` layout used for inputs as the
+    /// set is built, saving a second pass. Indices are written in
+    /// the order received, before any compaction, so the output is
+    /// neither sorted nor deduplicated.
+    pub fn build_and_tee<I, W>(cells: I, mut writer: W) -> std::io::Result<Self>
+    where
+        I: IntoIterator<Item = Cell>,
+        W: Write,
+    {
+        let mut set = HexTreeMap::with_compactor(SetCompactor);
+        for cell in cells {
+            writer.write_all(&cell.into_raw().to_le_bytes())?;
+            set.insert(cell, ());
+        }
+        writer.flush()?;
+        Ok(set)
+    }
+
+    /// Returns a patch which transforms `self` into `newer` when
+    /// passed to [apply_patch][Self::apply_patch].
+    ///
+    /// The patch lists the cells present in only one of the two
+    /// sets: first a little-endian `u64` count of removed cells
+    /// followed by their raw indices, then the same for added cells.
+    pub fn diff_bytes(&self, newer: &HexTreeSet) -> Vec<u8> {
+        let old_cells: HashSet<Cell> = self.iter().map(|(cell, _)| cell).collect();
+        let new_cells: HashSet<Cell> = newer.iter().map(|(cell, _)| cell).collect();
+        let removed: Vec<Cell> = self
+            .iter()
+            .map(|(cell, _)| cell)
+            .filter(|cell| !new_cells.contains(cell))
+            .collect();
+        let added: Vec<Cell> = newer
+            .iter()
+            .map(|(cell, _)| cell)
+            .filter(|cell| !old_cells.contains(cell))
+            .collect();
+        let mut patch = Vec::with_capacity(8 * (2 + removed.len() + added.len()));
+        for cells in [removed, added].iter() {
+            patch.extend_from_slice(&(cells.len() as u64).to_le_bytes());
+            for cell in cells {
+                patch.extend_from_slice(&cell.into_raw().to_le_bytes());
+            }
+        }
+        patch
+    }
+
+    /// Applies a patch produced by [diff_bytes][Self::diff_bytes].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error, leaving `self` unchanged, if the patch is
+    /// malformed or removes a cell `self` does not contain.
+    pub fn apply_patch(&mut self, patch: &[u8]) -> Result {
+        fn read_cells(rdr: &mut &[u8]) -> Result<Vec<Cell>> {
+            let mut read_u64 = || -> Result<u64> {
+                if rdr.len() < 8 {
+                    return Err(Error::InvalidPatch);
+                }
+                let (head, tail) = rdr.split_at(8);
+                *rdr = tail;
+                Ok(u64::from_le_bytes(head.try_into().expect("we split at 8")))
+            };
+            let count = read_u64()?;
+            (0..count)
+                .map(|_| read_u64().and_then(Cell::from_raw))
+                .collect()
+        }
+
+        let rdr = &mut &patch[..];
+        let removed = read_cells(rdr)?;
+        let added = read_cells(rdr)?;
+        if !rdr.is_empty() {
+            return Err(Error::InvalidPatch);
+        }
+        if !removed
+            .iter()
+            .all(|&cell| matches!(self.get_raw(cell), Some((leaf, Node::Leaf(_))) if leaf == cell))
+        {
+            return Err(Error::InvalidPatch);
+        }
+        for cell in removed {
+            self.remove_leaf(cell);
+        }
+        for cell in added {
+            self.insert(cell, ());
+        }
+        Ok(())
+    }
+}
+
+/// Returns the number of children of parent `node`.
+fn child_count(node: &Node<()>) -> usize {
+    match node {
+        Node::Parent(children) => children.iter().flatten().count(),
+        Node::Leaf(_) => unreachable!("only parent nodes are coarsened"),
+    }
+}
+
+/// Returns the area, in res-15 cells, that turning `node` into a leaf
+/// would add, or `None` if any of its children are not leaves.
+fn coarsening_cost(cell: Cell, node: &Node<()>) -> Option<u64> {
+    match node {
+        Node::Leaf(_) => None,
+        Node::Parent(children) => {
+            let mut n_children = 0;
+            for child in children.iter().flatten() {
+                if let Node::Parent(_) = child.as_ref() {
+                    return None;
+                }
+                n_children += 1;
+            }
+            let max_children = if cell.is_pentagon() { 6 } else { 7 };
+            Some((max_children - n_children) * 7_u64.pow(14 - cell.res() as u32))
+        }
+    }
+}
+
+fn find_coarsening_candidates(
+    cell: Cell,
+    node: &Node<()>,
+    candidates: &mut BinaryHeap<Reverse<(u64, u64)>>,
+) {
+    if let Some(cost) = coarsening_cost(cell, node) {
+        candidates.push(Reverse((cost, cell.into_raw())));
+    } else if let Node::Parent(children) = node {
+        for (digit, child) in children.iter().enumerate() {
+            if let Some(child) = child.as_deref() {
+                let child_cell = cell
+                    .to_child(digit as u8)
+                    .expect("parents are never res 15");
+                find_coarsening_candidates(child_cell, child, candidates);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simplify_to_coalesces() {
+        let parent = Cell::from_raw(0x825997fffffffff).unwrap();
+        let child = |digit| parent.to_child(digit).unwrap();
+        let mut cells: Vec<Cell> = (0..6).map(child).collect();
+        cells.push(child(6).to_child(0).unwrap());
+        cells.push(child(6).to_child(1).unwrap());
+        let set: HexTreeSet = cells.iter().collect();
+        assert_eq!(set.len(), 8);
+
+        // Coarsening child 6 completes the family, which must then
+        // coalesce into `parent`.
+        let simplified = set.simplify_to(7);
+        let expected: HexTreeSet = std::iter::once(parent).collect();
+        assert_eq!(simplified, expected);
+        assert!(simplified.contains(parent));
+        assert_eq!(simplified.len(), 1);
+    }
+}
@@ -3,6 +3,9 @@
 
 mod cell;
 pub mod compaction;
+mod coverage;
+#[cfg(feature = "csv")]
+mod csv_import;
 mod digits;
 #[cfg(feature = "disktree")]
 pub mod disktree;
@@ -11,7 +14,9 @@ mod error;
 pub mod hex_tree_map;
 mod hex_tree_set;
 mod iteration;
+mod macros;
 mod node;
+mod node_ref;
 
 pub use crate::cell::Cell;
 pub use crate::hex_tree_map::HexTreeMap;
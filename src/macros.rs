@@ -0,0 +1,96 @@
+/// Creates a [HexTreeSet][crate::HexTreeSet] containing the given raw
+/// H3 indices.
+///
+/// # Panics
+///
+/// Panics if any index is not a valid H3 cell.
+///
+/// # Examples
+///
+/// ```
+/// use hextree::{hextree_set, Cell, HexTreeSet};
+///
+/// let set = hextree_set![0x8f2834734d10669, 0x85283473fffffff];
+/// assert_eq!(set.len(), 1);
+/// assert!(set.contains(Cell::from_raw(0x85283473fffffff).unwrap()));
+/// ```
+#[macro_export]
+macro_rules! hextree_set {
+    ($($idx:expr),* $(,)?) => {
+        [$($idx),*]
+            .iter()
+            .map(|&idx: &u64| {
+                $crate::Cell::from_raw(idx).expect("valid H3 cell index")
+            })
+            .collect::<$crate::HexTreeSet>()
+    };
+}
+
+/// Creates a [HexTreeMap][crate::HexTreeMap] from raw H3 indices and
+/// their values.
+///
+/// # Panics
+///
+/// Panics if any index is not a valid H3 cell.
+///
+/// # Examples
+///
+/// ```
+/// use hextree::{hextree_map, Cell, HexTreeMap};
+///
+/// let map: HexTreeMap<&str> = hextree_map! {
+///     0x85283473fffffff => "sf",
+///     0x8f2834734d10669 => "also sf",
+/// };
+/// assert_eq!(map[Cell::from_raw(0x8f2834734d10669).unwrap()], "sf");
+/// ```
+#[macro_export]
+macro_rules! hextree_map {
+    ($($idx:expr => $value:expr),* $(,)?) => {
+        vec![$(($idx, $value)),*]
+            .into_iter()
+            .map(|(idx, value): (u64, _)| {
+                ($crate::Cell::from_raw(idx).expect("valid H3 cell index"), value)
+            })
+            .collect::<$crate::HexTreeMap<_>>()
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Cell, HexTreeMap, HexTreeSet};
+
+    #[test]
+    fn test_hextree_set() {
+        let indices = [0x85283473fffffff, 0x825997fffffffff, 0x8f2834734d10669];
+        let manual: HexTreeSet = indices
+            .iter()
+            .map(|&idx| Cell::from_raw(idx).unwrap())
+            .collect();
+        assert_eq!(
+            hextree_set![0x85283473fffffff, 0x825997fffffffff, 0x8f2834734d10669,],
+            manual
+        );
+        assert!(hextree_set![].is_empty());
+    }
+
+    #[test]
+    fn test_hextree_map() {
+        let mut manual = HexTreeMap::new();
+        manual.insert(Cell::from_raw(0x85283473fffffff).unwrap(), 1);
+        manual.insert(Cell::from_raw(0x825997fffffffff).unwrap(), 2);
+        assert_eq!(
+            hextree_map! {
+                0x85283473fffffff => 1,
+                0x825997fffffffff => 2,
+            },
+            manual
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_hextree_set_invalid_index() {
+        let _ = hextree_set![0];
+    }
+}
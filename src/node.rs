@@ -1,4 +1,5 @@
-use crate::{compaction::Compactor, digits::Digits, Cell};
+use crate::{cell::Descendants, compaction::Compactor, digits::Digits, Cell};
+use std::{collections::BTreeMap, ops::RangeInclusive};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -20,6 +21,15 @@ impl<V> Node<V> {
         }
     }
 
+    pub(crate) fn memory_by_resolution(&self, res: u8, sizes: &mut BTreeMap<u8, usize>) {
+        *sizes.entry(res).or_insert(0) += std::mem::size_of::<Self>();
+        if let Self::Parent(children) = self {
+            for child in children.iter().flatten() {
+                child.memory_by_resolution(res + 1, sizes);
+            }
+        }
+    }
+
     pub(crate) fn insert<C>(
         &mut self,
         cell: Cell,
@@ -51,6 +61,23 @@ impl<V> Node<V> {
         self.coalesce(cell.to_parent(res).unwrap(), compactor);
     }
 
+    pub(crate) fn compact<C>(&mut self, cell: Cell, compactor: &mut C)
+    where
+        C: Compactor<V>,
+    {
+        if let Self::Parent(children) = self {
+            for (digit, child) in children.iter_mut().enumerate() {
+                if let Some(child) = child.as_deref_mut() {
+                    let child_cell = cell
+                        .to_child(digit as u8)
+                        .expect("parents are never res 15");
+                    child.compact(child_cell, compactor);
+                }
+            }
+            self.coalesce(cell, compactor);
+        }
+    }
+
     pub(crate) fn coalesce<C>(&mut self, cell: Cell, compactor: &mut C)
     where
         C: Compactor<V>,
@@ -72,6 +99,174 @@ impl<V> Node<V> {
         };
     }
 
+    /// Removes the leaf exactly at the end of `digits` from `slot`,
+    /// pruning any parents left without children.
+    pub(crate) fn remove_leaf(slot: &mut Option<Box<Node<V>>>, mut digits: Digits) -> Option<V> {
+        match (digits.next(), slot.as_deref_mut()?) {
+            (None, Self::Leaf(_)) => match slot.take().map(|node| *node) {
+                Some(Self::Leaf(value)) => Some(value),
+                _ => unreachable!("we just matched a leaf"),
+            },
+            (Some(digit), Self::Parent(children)) => {
+                let value = Self::remove_leaf(&mut children[digit as usize], digits)?;
+                if children.iter().all(Option::is_none) {
+                    *slot = None;
+                }
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+
+    /// Visits every leaf under `slot`, removing those for which `f`
+    /// returns `false` and pruning any parents left without children.
+    pub(crate) fn retain<F>(slot: &mut Option<Box<Node<V>>>, cell: Cell, f: &mut F)
+    where
+        F: FnMut(Cell, &mut V) -> bool,
+    {
+        let keep = match slot.as_deref_mut() {
+            None => return,
+            Some(Self::Leaf(value)) => f(cell, value),
+            Some(Self::Parent(children)) => {
+                for (digit, child) in children.iter_mut().enumerate() {
+                    if child.is_some() {
+                        let child_cell = cell
+                            .to_child(digit as u8)
+                            .expect("parents are never res 15");
+                        Self::retain(child, child_cell, f);
+                    }
+                }
+                children.iter().any(Option::is_some)
+            }
+        };
+        if !keep {
+            *slot = None;
+        }
+    }
+
+    /// Removes everything covered by `other` from `slot`, splitting
+    /// leaves only partially covered by `other` into their children.
+    ///
+    /// Returns whether anything was removed.
+    pub(crate) fn subtract<W>(slot: &mut Option<Box<Node<V>>>, cell: Cell, other: &Node<W>) -> bool
+    where
+        V: Clone,
+    {
+        let other_children = match other {
+            Node::Leaf(_) => return slot.take().is_some(),
+            Node::Parent(other_children) => other_children,
+        };
+        let node = match slot.as_deref_mut() {
+            None => return false,
+            Some(node) => node,
+        };
+        if let Self::Leaf(value) = node {
+            let is_pentagon = cell.is_pentagon();
+            let mut children = [None, None, None, None, None, None, None];
+            for (digit, child) in children.iter_mut().enumerate() {
+                if !(is_pentagon && digit == 1) {
+                    *child = Some(Box::new(Self::Leaf(value.clone())));
+                }
+            }
+            *node = Self::Parent(children);
+        }
+        let children = match node {
+            Self::Parent(children) => children,
+            Self::Leaf(_) => unreachable!("leaves were just split"),
+        };
+        let mut removed = false;
+        for (digit, (child, other_child)) in children.iter_mut().zip(other_children).enumerate() {
+            if let Some(other_child) = other_child {
+                let child_cell = cell
+                    .to_child(digit as u8)
+                    .expect("parents are never res 15");
+                removed |= Self::subtract(child, child_cell, other_child);
+            }
+        }
+        if children.iter().all(Option::is_none) {
+            *slot = None;
+        }
+        removed
+    }
+
+    /// Returns whether this node, at `cell`, covers all of `cell`,
+    /// whether as a leaf or through uncompacted descendants.
+    pub(crate) fn is_full(&self, cell: Cell) -> bool {
+        match self {
+            Self::Leaf(_) => true,
+            Self::Parent(children) => {
+                let is_pentagon = cell.is_pentagon();
+                children
+                    .iter()
+                    .enumerate()
+                    .all(|(digit, child)| match child {
+                        Some(child) => child.is_full(
+                            cell.to_child(digit as u8)
+                                .expect("parents are never res 15"),
+                        ),
+                        None => is_pentagon && digit == 1,
+                    })
+            }
+        }
+    }
+
+    /// Appends to `cells` the coarsest cells, none coarser than
+    /// `res`, which exactly cover this node at `cell`.
+    pub(crate) fn covering_cells(&self, cell: Cell, res: u8, cells: &mut Vec<Cell>) {
+        if cell.res() >= res && self.is_full(cell) {
+            cells.push(cell);
+            return;
+        }
+        match self {
+            Self::Leaf(_) => cells.extend(Descendants::new(cell, res)),
+            Self::Parent(children) => {
+                for (digit, child) in children.iter().enumerate() {
+                    if let Some(child) = child {
+                        let child_cell = cell
+                            .to_child(digit as u8)
+                            .expect("parents are never res 15");
+                        child.covering_cells(child_cell, res, cells);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Appends to `cells` the leaves under this node, at `cell`,
+    /// whose raw indices fall in `range`, skipping subtrees which can
+    /// not have any.
+    pub(crate) fn leaves_in_index_range(
+        &self,
+        cell: Cell,
+        range: &RangeInclusive<u64>,
+        cells: &mut Vec<Cell>,
+    ) {
+        let may_overlap = (cell.res()..16)
+            .map(|res| cell.descendant_index_range(res))
+            .take_while(|&(low, _)| low <= *range.end())
+            .any(|(_, high)| high >= *range.start());
+        if !may_overlap {
+            return;
+        }
+        match self {
+            Self::Leaf(_) => {
+                if range.contains(&cell.into_raw()) {
+                    cells.push(cell);
+                }
+            }
+            Self::Parent(children) => {
+                for (digit, child) in children.iter().enumerate() {
+                    if let Some(child) = child {
+                        let child_cell = cell
+                            .to_child(digit as u8)
+                            .expect("parents are never res 15");
+                        child.leaves_in_index_range(child_cell, range, cells);
+                    }
+                }
+            }
+        }
+    }
+
     pub(crate) fn value(&self) -> Option<&V> {
         match self {
             Self::Leaf(value) => Some(value),
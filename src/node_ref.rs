@@ -0,0 +1,84 @@
+//! Read-only access to a `HexTreeMap`'s nodes.
+
+use crate::{node::Node, Cell};
+
+/// A read-only handle to a node in a [HexTreeMap][crate::HexTreeMap].
+///
+/// Node handles are obtained from
+/// [roots][crate::HexTreeMap::roots] or
+/// [iter_nodes][crate::HexTreeMap::iter_nodes] and allow walking the
+/// tree's structure, including intermediate (non-leaf) nodes,
+/// without exposing its internals.
+pub struct NodeRef<'a, V> {
+    cell: Cell,
+    node: &'a Node<V>,
+}
+
+impl<'a, V> NodeRef<'a, V> {
+    pub(crate) fn new(cell: Cell, node: &'a Node<V>) -> Self {
+        Self { cell, node }
+    }
+
+    /// Returns the cell this node represents.
+    pub fn cell(&self) -> Cell {
+        self.cell
+    }
+
+    /// Returns this node's resolution.
+    ///
+    /// This is the same as its depth in the tree, where base
+    /// (res-0) nodes are at depth 0.
+    pub fn res(&self) -> u8 {
+        self.cell.res()
+    }
+
+    /// Returns `true` if this is a leaf (complete) node.
+    pub fn is_leaf(&self) -> bool {
+        matches!(self.node, Node::Leaf(_))
+    }
+
+    /// Returns this node's value if it is a leaf node.
+    pub fn value(&self) -> Option<&'a V> {
+        self.node.value()
+    }
+
+    /// Returns an iterator over this node's children in digit
+    /// order.
+    ///
+    /// Leaf nodes have no children.
+    pub fn children(&self) -> impl Iterator<Item = NodeRef<'a, V>> + 'a {
+        let cell = self.cell;
+        let children: &'a [Option<Box<Node<V>>>] = match self.node {
+            Node::Parent(children) => children,
+            Node::Leaf(_) => &[],
+        };
+        children
+            .iter()
+            .enumerate()
+            .filter_map(move |(digit, child)| {
+                child.as_deref().map(|node| {
+                    let child_cell = cell
+                        .to_child(digit as u8)
+                        .expect("parent nodes are never res 15");
+                    NodeRef::new(child_cell, node)
+                })
+            })
+    }
+}
+
+impl<V> Clone for NodeRef<'_, V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<V> Copy for NodeRef<'_, V> {}
+
+impl<V> std::fmt::Debug for NodeRef<'_, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NodeRef")
+            .field("cell", &self.cell)
+            .field("is_leaf", &self.is_leaf())
+            .finish()
+    }
+}
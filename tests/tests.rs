@@ -1,7 +1,7 @@
 use geo::coord;
 use h3_lorawan_regions as regions;
 use h3ron::H3Cell;
-use hextree::{compaction::EqCompactor, Cell, HexTreeMap, HexTreeSet};
+use hextree::{compaction::EqCompactor, hex_tree_map::NodeRef, Cell, HexTreeMap, HexTreeSet};
 
 /// Perform a linear search of `region` for `target` cell.
 fn naive_contains(region: &[Cell], target: Cell) -> bool {
@@ -158,3 +158,406 @@ fn test_compaction() {
     assert!(us915_nocompact_tree.contains(gulf_of_mexico));
     assert_eq!(us915_tree.len(), us915_nocompact_tree.len());
 }
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_par_contains() {
+    let (us915_tree, _) = from_indicies(regions::compact::US915);
+    let queries: Vec<Cell> = regions::nocompact::US915
+        .iter()
+        .chain(regions::nocompact::EU868.iter())
+        .map(|&idx| Cell::from_raw(idx).unwrap())
+        .collect();
+    let serial: Vec<bool> = queries.iter().map(|&c| us915_tree.contains(c)).collect();
+    let parallel = us915_tree.par_contains(&queries);
+    assert_eq!(serial, parallel);
+    assert!(parallel.iter().any(|&hit| hit));
+    assert!(parallel.iter().any(|&hit| !hit));
+}
+
+#[test]
+fn test_node_ref_traversal() {
+    fn count_leaves(node: NodeRef<'_, ()>) -> usize {
+        if node.is_leaf() {
+            1
+        } else {
+            node.children()
+                .map(|child| {
+                    assert_eq!(child.res(), node.res() + 1);
+                    assert_eq!(child.cell().to_parent(node.res()), Some(node.cell()));
+                    count_leaves(child)
+                })
+                .sum()
+        }
+    }
+
+    let (us915_tree, _) = from_indicies(regions::compact::US915);
+    assert!(us915_tree.roots().all(|root| root.res() == 0));
+    let leaf_count: usize = us915_tree.roots().map(count_leaves).sum();
+    assert_eq!(leaf_count, us915_tree.len());
+}
+
+#[test]
+fn test_simplify_to() {
+    let (us915_tree, us915_cells) = from_indicies(regions::compact::US915);
+    assert_eq!(us915_tree.simplify_to(us915_tree.len()), us915_tree);
+
+    for target in [10_000, 1_000, 100] {
+        let simplified = us915_tree.simplify_to(target);
+        assert!(simplified.len() <= target);
+        assert!(us915_cells.iter().all(|&cell| simplified.contains(cell)));
+        // Fully compacted, exactly like a freshly built set.
+        let rebuilt: HexTreeSet = simplified.iter().map(|(cell, _)| cell).collect();
+        assert_eq!(simplified, rebuilt);
+    }
+}
+
+#[test]
+fn test_uncompact() {
+    use h3o::{CellIndex, Resolution};
+    use std::convert::TryFrom;
+
+    let (us915_tree, us915_cells) = from_indicies(regions::compact::US915);
+    let res = 7;
+    let expected_count: u64 = us915_cells
+        .iter()
+        .filter(|cell| cell.res() <= res)
+        .map(|cell| {
+            CellIndex::try_from(cell.into_raw())
+                .unwrap()
+                .children_count(Resolution::try_from(res).unwrap())
+        })
+        .sum();
+    let mut uncompacted_count = 0;
+    for (cell, _) in us915_tree.uncompact(res) {
+        assert_eq!(cell.res(), res);
+        assert!(us915_tree.contains(cell));
+        uncompacted_count += 1;
+    }
+    assert_eq!(uncompacted_count, expected_count);
+
+    // Cells finer than the target resolution are skipped.
+    let min_res = us915_cells.iter().map(|cell| cell.res()).min().unwrap();
+    assert_eq!(
+        us915_tree.uncompact(min_res).count(),
+        us915_cells
+            .iter()
+            .filter(|cell| cell.res() == min_res)
+            .count()
+    );
+}
+
+#[test]
+fn test_contains_iter() {
+    let (us915_tree, _) = from_indicies(regions::compact::US915);
+    let queries = || {
+        regions::nocompact::US915
+            .iter()
+            .chain(regions::nocompact::EU868.iter())
+            .map(|&idx| Cell::from_raw(idx).unwrap())
+    };
+    assert!(us915_tree
+        .contains_iter(queries())
+        .eq(queries().map(|cell| us915_tree.contains(cell))));
+}
+
+#[test]
+fn test_diff_apply_patch() {
+    let (old, us915_cells) = from_indicies(regions::compact::US915);
+    let (_, eu868_cells) = from_indicies(regions::compact::EU868);
+    let new: HexTreeSet = us915_cells[100..].iter().chain(&eu868_cells).collect();
+
+    let patch = old.diff_bytes(&new);
+    let mut patched = old.clone();
+    patched.apply_patch(&patch).unwrap();
+    assert_eq!(patched, new);
+
+    // A patch never applies to a set missing the cells it removes.
+    let mut unpatched: HexTreeSet = std::iter::empty::<Cell>().collect();
+    assert!(unpatched.apply_patch(&patch).is_err());
+    assert!(unpatched.is_empty());
+    assert!(patched.apply_patch(&patch[..patch.len() - 1]).is_err());
+    assert_eq!(patched, new);
+}
+
+#[test]
+fn test_compaction_matches_h3() {
+    use byteorder::{LittleEndian as LE, ReadBytesExt};
+    use h3o::CellIndex;
+    use std::convert::TryFrom;
+
+    fn h3_compact(cells: &[Cell]) -> Vec<Cell> {
+        let mut cells: Vec<CellIndex> = cells
+            .iter()
+            .map(|cell| CellIndex::try_from(cell.into_raw()).unwrap())
+            .collect();
+        cells.sort();
+        cells.dedup();
+        let mut compacted: Vec<Cell> = CellIndex::compact(cells)
+            .unwrap()
+            .map(|ci| Cell::from_raw(u64::from(ci)).unwrap())
+            .collect();
+        compacted.sort_by_key(|cell| cell.into_raw());
+        compacted
+    }
+
+    fn hextree_compact(cells: &[Cell]) -> Vec<Cell> {
+        let set: HexTreeSet = cells.iter().collect();
+        let mut compacted: Vec<Cell> = set.iter().map(|(cell, _)| cell).collect();
+        compacted.sort_by_key(|cell| cell.into_raw());
+        compacted
+    }
+
+    let monaco_cells = {
+        let idx_bytes = include_bytes!("../assets/monaco.res12.h3idx");
+        let rdr = &mut idx_bytes.as_slice();
+        let mut cells = Vec::new();
+        while let Ok(idx) = rdr.read_u64::<LE>() {
+            cells.push(Cell::from_raw(idx).unwrap());
+        }
+        cells
+    };
+    assert_eq!(hextree_compact(&monaco_cells), h3_compact(&monaco_cells));
+
+    let (_, us915_cells) = from_indicies(regions::nocompact::US915);
+    assert_eq!(hextree_compact(&us915_cells), h3_compact(&us915_cells));
+
+    // All res-3 descendants of a pentagon.
+    let pentagon = CellIndex::try_from(0x8009fffffffffff).unwrap();
+    let pentagon_cells: Vec<Cell> = pentagon
+        .children(h3o::Resolution::Three)
+        .map(|ci| Cell::from_raw(u64::from(ci)).unwrap())
+        .collect();
+    assert_eq!(
+        hextree_compact(&pentagon_cells),
+        h3_compact(&pentagon_cells)
+    );
+}
+
+#[test]
+fn test_subtract() {
+    let (mut us915_tree, us915_cells) = from_indicies(regions::compact::US915);
+    let (eu868_tree, _) = from_indicies(regions::compact::EU868);
+    let original = us915_tree.clone();
+    assert!(!us915_tree.subtract(&eu868_tree));
+    assert_eq!(us915_tree, original);
+
+    let (removed_tree, removed_cells) = from_indicies(&regions::compact::US915[..100]);
+    assert!(us915_tree.subtract(&removed_tree));
+    assert!(removed_cells.iter().all(|&cell| !us915_tree.contains(cell)));
+    assert!(us915_cells[100..]
+        .iter()
+        .all(|&cell| us915_tree.contains(cell)));
+    assert!(!us915_tree.subtract(&removed_tree));
+}
+
+#[test]
+fn test_from_cells() {
+    let (us915_tree, us915_cells) = from_indicies(regions::compact::US915);
+    let from_slice: HexTreeSet = us915_cells.as_slice().into();
+    assert_eq!(from_slice, us915_tree);
+    let from_vec: HexTreeSet = us915_cells.into();
+    assert_eq!(from_vec, us915_tree);
+    assert!(HexTreeSet::from(Vec::new()).is_empty());
+}
+
+#[test]
+fn test_first_difference() {
+    let (us915_tree, us915_cells) = from_indicies(regions::compact::US915);
+    assert_eq!(us915_tree.first_difference(&us915_tree.clone()), None);
+    let removed = us915_cells[us915_cells.len() / 2];
+    let reduced: HexTreeSet = us915_cells
+        .iter()
+        .filter(|&&cell| cell != removed)
+        .collect();
+    assert_eq!(us915_tree.first_difference(&reduced), Some(removed));
+    assert_eq!(reduced.first_difference(&us915_tree), Some(removed));
+}
+
+#[test]
+fn test_covering_cells() {
+    let (us915_tree, us915_cells) = from_indicies(regions::compact::US915);
+    let max_res = us915_cells.iter().map(|cell| cell.res()).max().unwrap();
+    let uniform_count = us915_tree.added_count(&HexTreeMap::<()>::new(), max_res);
+    for res in [0, 3, max_res] {
+        let covering = us915_tree.covering_cells(res);
+        assert!(covering.iter().all(|cell| cell.res() >= res));
+        assert!(covering.len() as u64 <= uniform_count);
+        let covering_tree: HexTreeSet = covering.iter().collect();
+        assert_eq!(covering_tree.first_difference(&us915_tree), None);
+    }
+    assert_eq!(us915_tree.covering_cells(0).len(), us915_tree.len());
+}
+
+#[test]
+fn test_iter_from_pagination() {
+    let (us915_tree, _) = from_indicies(regions::compact::US915);
+    let page_size = 1000;
+    let mut pages: Vec<Cell> = us915_tree
+        .iter()
+        .take(page_size)
+        .map(|(cell, _)| cell)
+        .collect();
+    while let Some(&last) = pages.last() {
+        let page: Vec<Cell> = us915_tree
+            .iter_from(last)
+            .take(page_size)
+            .map(|(cell, _)| cell)
+            .collect();
+        if page.is_empty() {
+            break;
+        }
+        pages.extend(page);
+    }
+    assert!(pages
+        .iter()
+        .copied()
+        .eq(us915_tree.iter().map(|(cell, _)| cell)));
+}
+
+#[test]
+fn test_contains_raw() {
+    let (us915_tree, _) = from_indicies(regions::compact::US915);
+    for &idx in regions::nocompact::US915 {
+        assert!(us915_tree.contains_raw(idx).unwrap());
+    }
+    for &idx in regions::nocompact::EU868 {
+        assert!(!us915_tree.contains_raw(idx).unwrap());
+    }
+    assert!(us915_tree.contains_raw(u64::MAX).is_err());
+}
+
+#[test]
+fn test_leaves_in_index_range() {
+    let (us915_tree, _) = from_indicies(regions::compact::US915);
+    let mut raws: Vec<u64> = us915_tree.iter().map(|(cell, _)| cell.into_raw()).collect();
+    raws.sort_unstable();
+    let (start, end) = (raws[raws.len() / 3], raws[2 * raws.len() / 3]);
+    let mut in_range = us915_tree.leaves_in_index_range(start, end);
+    in_range.sort_by_key(|cell| cell.into_raw());
+    assert!(in_range
+        .iter()
+        .map(|cell| cell.into_raw())
+        .eq(raws[raws.len() / 3..=2 * raws.len() / 3].iter().copied()));
+}
+
+#[test]
+fn test_compaction_savings() {
+    use hextree::compaction::SetCompactor;
+
+    let us915_uncompacted: HexTreeMap<()> = regions::nocompact::US915
+        .iter()
+        .map(|&idx| (Cell::from_raw(idx).unwrap(), ()))
+        .collect();
+    let mut us915_tree = us915_uncompacted.replace_compactor(SetCompactor);
+    let bytes = |tree: &HexTreeSet| tree.memory_by_resolution().values().sum::<usize>();
+    let (len_before, bytes_before) = (us915_tree.len(), bytes(&us915_tree));
+    let (cells_removed, bytes_saved) = us915_tree.compaction_savings();
+    assert_ne!(cells_removed, 0);
+    us915_tree.compact();
+    assert_eq!(cells_removed, len_before - us915_tree.len());
+    assert_eq!(bytes_saved, bytes_before - bytes(&us915_tree));
+    assert_eq!(us915_tree.compaction_savings(), (0, 0));
+}
+
+#[test]
+fn test_build_and_tee() {
+    use byteorder::{LittleEndian as LE, ReadBytesExt};
+
+    let (us915_tree, _) = from_indicies(regions::nocompact::US915);
+    let mut written = Vec::new();
+    let teed = HexTreeSet::build_and_tee(
+        regions::nocompact::US915
+            .iter()
+            .map(|&idx| Cell::from_raw(idx).unwrap()),
+        &mut written,
+    )
+    .unwrap();
+    assert_eq!(teed, us915_tree);
+
+    let rdr = &mut written.as_slice();
+    let mut indices = Vec::new();
+    while let Ok(idx) = rdr.read_u64::<LE>() {
+        indices.push(idx);
+    }
+    assert_eq!(indices, regions::nocompact::US915);
+}
+
+#[test]
+fn test_contains_mixed_resolutions() {
+    use h3o::{CellIndex, Resolution};
+    use std::convert::TryFrom;
+
+    let (us915_tree, us915_compact_cells) = from_indicies(regions::compact::US915);
+    let (_, us915_cells) = from_indicies(regions::nocompact::US915);
+    let (_, eu868_cells) = from_indicies(regions::nocompact::EU868);
+    let queries: Vec<Cell> = us915_cells
+        .iter()
+        .chain(&eu868_cells)
+        .enumerate()
+        .map(|(i, cell)| {
+            let res = Resolution::try_from(cell.res().max(7 + (i % 9) as u8)).unwrap();
+            let center_child = CellIndex::try_from(cell.into_raw())
+                .unwrap()
+                .center_child(res)
+                .unwrap();
+            Cell::from_raw(u64::from(center_child)).unwrap()
+        })
+        .collect();
+    assert!(queries.iter().any(|cell| cell.res() <= 7));
+    assert!(queries.iter().any(|cell| cell.res() == 15));
+
+    let batch: Vec<bool> = us915_tree.contains_iter(queries.iter().copied()).collect();
+    assert_eq!(batch.len(), queries.len());
+    // Every US915 query is a hit and every EU868 query a miss.
+    for (i, (&query, &hit)) in queries.iter().zip(&batch).enumerate() {
+        assert_eq!(hit, i < us915_cells.len(), "{query:?}");
+    }
+    // `naive_contains` scans the whole region, so only check a sample
+    // which still spans every query resolution.
+    for (&query, &hit) in queries.iter().zip(&batch).step_by(101) {
+        assert_eq!(
+            hit,
+            naive_contains(&us915_compact_cells, query),
+            "{query:?}"
+        );
+    }
+}
+
+#[test]
+fn test_shared_root_cells() {
+    let (us915_tree, us915_cells) = from_indicies(regions::compact::US915);
+    let (eu868_tree, eu868_cells) = from_indicies(regions::compact::EU868);
+    assert!(us915_tree.shared_root_cells(&eu868_tree).is_empty());
+
+    let mixed: HexTreeSet = us915_cells[..10].iter().chain(&eu868_cells).collect();
+    let mut expected: Vec<Cell> = us915_cells[..10]
+        .iter()
+        .map(|cell| cell.to_parent(0).unwrap())
+        .collect();
+    expected.sort_by_key(|cell| cell.into_raw());
+    expected.dedup();
+    assert_eq!(us915_tree.shared_root_cells(&mixed), expected);
+    assert_eq!(
+        us915_tree.shared_root_cells(&us915_tree),
+        us915_tree
+            .roots()
+            .map(|root| root.cell())
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_iter_nodes() {
+    let (us915_tree, _) = from_indicies(regions::compact::US915);
+    let leaves: Vec<Cell> = us915_tree
+        .iter_nodes()
+        .filter(|node| node.is_leaf())
+        .map(|node| node.cell())
+        .collect();
+    assert!(leaves
+        .iter()
+        .copied()
+        .eq(us915_tree.iter().map(|(cell, _)| cell)));
+    assert!(us915_tree.iter_nodes().count() > leaves.len());
+}